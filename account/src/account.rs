@@ -1,111 +1,424 @@
-use super::{BlindPair, KeyPair};
+use super::address::{Address, Network};
+use super::{error::AccountError, BlindPair, KeyPair};
+use ct_utils::codec::{Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
 use ct_utils::hash::Hasher;
-use ct_utils::{bytes_to_scalar, generator::BASEPOINT_G2, get_random_scalar, point_to_bytes};
+use ct_utils::{domain_hash_to_scalar, generator::BASEPOINT_G2, get_random_scalar, point_to_bytes};
 use curve25519_dalek::traits::MultiscalarMul;
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
 
+//the view key finds incoming outputs and decrypts their amounts, the spend key is
+//additionally needed to sign for them. Handing out the `ViewAccount` alone gives a
+//watch-only wallet.
 #[derive(Debug, Clone)]
-pub struct Account(pub KeyPair);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Account {
+    view: KeyPair,
+    spend: KeyPair,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Account {
-    pub fn get_public_key(&self) -> RistrettoPoint {
-        self.0.public_key
+    pub fn new() -> Account {
+        Account {
+            view: gen_key_pair(),
+            spend: gen_key_pair(),
+        }
     }
 
-    pub fn get_private_key(&self) -> Scalar {
-        self.0.private_key
+    //the single key pair of an account from before the view/spend split serves as
+    //both, so outputs paid to it with `Derivation::Legacy` are found and spendable
+    pub fn from_legacy_key(private_key: Scalar) -> Account {
+        let key_pair = KeyPair {
+            private_key,
+            public_key: RistrettoPoint::multiscalar_mul(&[private_key], &[*BASEPOINT_G2]),
+        };
+        Account {
+            view: key_pair.clone(),
+            spend: key_pair,
+        }
     }
 
-    pub fn new() -> Account {
-        let private_key = get_random_scalar();
-        let public_key = RistrettoPoint::multiscalar_mul(&[private_key], &[*BASEPOINT_G2]);
+    pub fn get_view_public_key(&self) -> RistrettoPoint {
+        self.view.public_key
+    }
 
-        Account(KeyPair {
-            private_key,
-            public_key,
-        })
+    pub fn get_view_private_key(&self) -> Scalar {
+        self.view.private_key
     }
 
-    pub fn to_address(&self) -> Vec<u8> {
-        let pubkey_bytes = point_to_bytes(&self.get_public_key());
-        Hasher::sha_256(&pubkey_bytes)
+    pub fn get_spend_public_key(&self) -> RistrettoPoint {
+        self.spend.public_key
     }
 
-    //genetator one_time_account,and the ECDH algorithm is used to generate symmetric key
-    pub fn gen_one_time_account(&self) -> (OneTimeAccount, BlindPair, Vec<u8>) {
-        let r = get_random_scalar();
-        let r_point = RistrettoPoint::multiscalar_mul(&[r], &[*BASEPOINT_G2]);
+    pub fn get_spend_private_key(&self) -> Scalar {
+        self.spend.private_key
+    }
 
-        let one_time_account = RistrettoPoint::multiscalar_mul(
-            &[
-                bytes_to_scalar(&point_to_bytes(&RistrettoPoint::multiscalar_mul(
-                    &[r],
-                    &[self.get_public_key()],
-                )))
-                .unwrap(),
-            ],
-            &[self.get_public_key()],
-        );
+    pub fn to_view_account(&self) -> ViewAccount {
+        ViewAccount {
+            view_private_key: self.view.private_key,
+            spend_public_key: self.spend.public_key,
+        }
+    }
 
-        let symmetric_key = Hasher::sha_256(&point_to_bytes(&RistrettoPoint::multiscalar_mul(
-            &[r],
-            &[one_time_account],
-        )));
+    pub fn to_address(&self, network: Network) -> Address {
+        Address::new(
+            network,
+            self.get_view_public_key(),
+            self.get_spend_public_key(),
+        )
+    }
 
-        (
-            OneTimeAccount(one_time_account),
-            BlindPair(KeyPair {
-                private_key: r,
-                public_key: r_point,
-            }),
-            symmetric_key,
+    //genetator one_time_account for the output at `output_index` of its
+    //transaction, and the ECDH algorithm is used to generate symmetric key
+    pub fn gen_one_time_account(&self, output_index: u32) -> (OneTimeAccount, BlindPair, Vec<u8>) {
+        gen_one_time_account(
+            self.get_view_public_key(),
+            self.get_spend_public_key(),
+            Derivation::Indexed(output_index),
         )
     }
 }
 
-#[derive(Copy, Clone)]
-pub struct OneTimeAccount(RistrettoPoint);
+//anything that can be paid: both key sets and a parsed `Address`
+pub trait Recipient {
+    fn gen_one_time_account(&self, output_index: u32) -> (OneTimeAccount, BlindPair, Vec<u8>);
+}
+
+impl Recipient for Account {
+    fn gen_one_time_account(&self, output_index: u32) -> (OneTimeAccount, BlindPair, Vec<u8>) {
+        Account::gen_one_time_account(self, output_index)
+    }
+}
+
+impl Recipient for ViewAccount {
+    fn gen_one_time_account(&self, output_index: u32) -> (OneTimeAccount, BlindPair, Vec<u8>) {
+        gen_one_time_account(
+            self.get_view_public_key(),
+            self.spend_public_key,
+            Derivation::Indexed(output_index),
+        )
+    }
+}
+
+impl Recipient for Address {
+    fn gen_one_time_account(&self, output_index: u32) -> (OneTimeAccount, BlindPair, Vec<u8>) {
+        Address::gen_one_time_account(self, output_index)
+    }
+}
+
+//view private key and spend public key: enough to recognize outputs and read
+//their amounts, not enough to spend them
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ViewAccount {
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
+    view_private_key: Scalar,
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
+    spend_public_key: RistrettoPoint,
+}
+
+impl ViewAccount {
+    pub fn get_view_public_key(&self) -> RistrettoPoint {
+        RistrettoPoint::multiscalar_mul(&[self.view_private_key], &[*BASEPOINT_G2])
+    }
+
+    pub fn get_view_private_key(&self) -> Scalar {
+        self.view_private_key
+    }
+
+    pub fn get_spend_public_key(&self) -> RistrettoPoint {
+        self.spend_public_key
+    }
+
+    pub fn to_address(&self, network: Network) -> Address {
+        Address::new(network, self.get_view_public_key(), self.spend_public_key)
+    }
+}
+
+fn gen_key_pair() -> KeyPair {
+    let private_key = get_random_scalar();
+    KeyPair {
+        private_key,
+        public_key: RistrettoPoint::multiscalar_mul(&[private_key], &[*BASEPOINT_G2]),
+    }
+}
+
+//how an output turns the shared secret r*V = v*R between payer and receiver
+//into the scalar offset of its one-time account. Every output records its own,
+//so outputs created before the indexed derivation are still recognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Derivation {
+    //P = x*S with x the raw, unreduced bytes of r*V, as outputs were built before
+    //the indexed derivation. With V = S it is the single-key original.
+    Legacy,
+    //P = Hs("ct_one_time_account", r*V || index)*G2 + S from a 512-bit hash,
+    //`index` is the output's position in its transaction
+    Indexed(u32),
+}
+
+impl Derivation {
+    const LEGACY_TAG: u8 = 1;
+    const INDEXED_TAG: u8 = 2;
+    const DOMAIN: &'static [u8] = b"ct_one_time_account";
+
+    fn scalar(&self, shared_point: &RistrettoPoint) -> Scalar {
+        match self {
+            Derivation::Legacy => Scalar::from_bits(shared_point.compress().to_bytes()),
+            Derivation::Indexed(index) => {
+                let mut writer = Writer::new();
+                shared_point.encode(&mut writer);
+                writer.write_u32(*index);
+                domain_hash_to_scalar(Self::DOMAIN, &writer.into_bytes())
+            }
+        }
+    }
+
+    fn one_time_account(
+        &self,
+        shared_point: &RistrettoPoint,
+        spend_public_key: RistrettoPoint,
+    ) -> RistrettoPoint {
+        let scalar = self.scalar(shared_point);
+        match self {
+            Derivation::Legacy => RistrettoPoint::multiscalar_mul(&[scalar], &[spend_public_key]),
+            Derivation::Indexed(_) => RistrettoPoint::multiscalar_mul(
+                &[scalar, Scalar::one()],
+                &[*BASEPOINT_G2, spend_public_key],
+            ),
+        }
+    }
+
+    fn private_key(&self, shared_point: &RistrettoPoint, spend_private_key: Scalar) -> Scalar {
+        let scalar = self.scalar(shared_point);
+        match self {
+            Derivation::Legacy => scalar * spend_private_key,
+            Derivation::Indexed(_) => scalar + spend_private_key,
+        }
+    }
+}
+
+impl Encode for Derivation {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            Derivation::Legacy => writer.write_u8(Self::LEGACY_TAG),
+            Derivation::Indexed(index) => {
+                writer.write_u8(Self::INDEXED_TAG);
+                writer.write_u32(*index);
+            }
+        }
+    }
+}
+
+impl Decode for Derivation {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        match reader.read_u8()? {
+            Self::LEGACY_TAG => Ok(Derivation::Legacy),
+            Self::INDEXED_TAG => Ok(Derivation::Indexed(reader.read_u32()?)),
+            tag => Err(UtilsError::UnknownTag {
+                kind: "derivation",
+                tag,
+            }),
+        }
+    }
+}
+
+//P from r*V and S as `derivation` says, only the receiver's public keys are
+//needed, so both `Account` and a parsed `Address` can be paid. The amount key
+//comes from the same shared secret, so the view key alone can decrypt it.
+pub(crate) fn gen_one_time_account(
+    view_public_key: RistrettoPoint,
+    spend_public_key: RistrettoPoint,
+    derivation: Derivation,
+) -> (OneTimeAccount, BlindPair, Vec<u8>) {
+    let r = get_random_scalar();
+    let r_point = RistrettoPoint::multiscalar_mul(&[r], &[*BASEPOINT_G2]);
+    let shared_point = RistrettoPoint::multiscalar_mul(&[r], &[view_public_key]);
+
+    let one_time_account = derivation.one_time_account(&shared_point, spend_public_key);
+    let symmetric_key = Hasher::sha_256(&point_to_bytes(&shared_point));
+
+    (
+        OneTimeAccount(one_time_account),
+        BlindPair(KeyPair {
+            private_key: r,
+            public_key: r_point,
+        }),
+        symmetric_key,
+    )
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OneTimeAccount(
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))] RistrettoPoint,
+);
 
 impl OneTimeAccount {
     pub fn get_point(&self) -> RistrettoPoint {
         self.0
     }
 
-    // extract the private key from the one-time-address
+    //view key check: P rebuilt from v*R and S
+    pub fn belongs_to(
+        &self,
+        view_account: &ViewAccount,
+        blind_point: RistrettoPoint,
+        derivation: Derivation,
+    ) -> bool {
+        let shared_point =
+            RistrettoPoint::multiscalar_mul(&[view_account.get_view_private_key()], &[blind_point]);
+        derivation.one_time_account(&shared_point, view_account.get_spend_public_key())
+            == self.get_point()
+    }
+
+    // extract the private key from the one-time-address, Hs(v*R) + s or x*s for legacy
     pub fn get_private_key(
         &self,
         account: &Account,
         blind_point: RistrettoPoint,
-    ) -> Result<Scalar, &'static str> {
-        let key = match bytes_to_scalar(&point_to_bytes(&RistrettoPoint::multiscalar_mul(
-            &[account.get_private_key()],
-            &[blind_point],
-        ))) {
-            Ok(v) => v * account.get_private_key(),
-            Err(_) => return Err("parse err"),
-        };
+        derivation: Derivation,
+    ) -> Result<Scalar, AccountError> {
+        let shared_point =
+            RistrettoPoint::multiscalar_mul(&[account.get_view_private_key()], &[blind_point]);
+        let key = derivation.private_key(&shared_point, account.get_spend_private_key());
 
         let expect_point = RistrettoPoint::multiscalar_mul(&[key], &[*BASEPOINT_G2]);
         if expect_point == self.get_point() {
             return Ok(key);
         }
 
-        Err("this one time account not belong to you")
+        Err(AccountError::NotOwner)
+    }
+}
+
+impl Encode for OneTimeAccount {
+    fn encode(&self, writer: &mut Writer) {
+        self.0.encode(writer);
+    }
+}
+
+impl Decode for OneTimeAccount {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(OneTimeAccount(RistrettoPoint::decode(reader)?))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ct_utils::bytes_to_scalar;
 
     #[test]
     fn one_time_account_test() {
         let account = Account::new();
-        let (one_time_account, blind_secret, _) = account.gen_one_time_account();
+        let (one_time_account, blind_secret, _) = account.gen_one_time_account(3);
+        let derivation = Derivation::Indexed(3);
         let key = one_time_account
-            .get_private_key(&account, blind_secret.get_blind_point())
+            .get_private_key(&account, blind_secret.get_blind_point(), derivation)
             .unwrap();
         let expect_one_time_account = RistrettoPoint::multiscalar_mul(&[key], &[*BASEPOINT_G2]);
         assert_eq!(one_time_account.get_point(), expect_one_time_account);
+        assert!(one_time_account.belongs_to(
+            &account.to_view_account(),
+            blind_secret.get_blind_point(),
+            derivation
+        ));
+
+        let other = Account::new();
+        assert!(!one_time_account.belongs_to(
+            &other.to_view_account(),
+            blind_secret.get_blind_point(),
+            derivation
+        ));
+        assert!(one_time_account
+            .get_private_key(&other, blind_secret.get_blind_point(), derivation)
+            .is_err());
+    }
+
+    #[test]
+    fn derivation_test() {
+        let account = Account::new();
+        let view_account = account.to_view_account();
+        let (indexed, blind_secret, _) = account.gen_one_time_account(1);
+        let blind_point = blind_secret.get_blind_point();
+        //the index and the derivation version are part of the key
+        assert!(indexed.belongs_to(&view_account, blind_point, Derivation::Indexed(1)));
+        assert!(!indexed.belongs_to(&view_account, blind_point, Derivation::Indexed(0)));
+        assert!(!indexed.belongs_to(&view_account, blind_point, Derivation::Legacy));
+
+        //outputs created with the old derivation are still recognized and spendable
+        let (legacy, blind_secret, _) = gen_one_time_account(
+            account.get_view_public_key(),
+            account.get_spend_public_key(),
+            Derivation::Legacy,
+        );
+        let blind_point = blind_secret.get_blind_point();
+        assert!(legacy.belongs_to(&view_account, blind_point, Derivation::Legacy));
+        assert!(!legacy.belongs_to(&view_account, blind_point, Derivation::Indexed(0)));
+        let key = legacy
+            .get_private_key(&account, blind_point, Derivation::Legacy)
+            .unwrap();
+        assert_eq!(
+            RistrettoPoint::multiscalar_mul(&[key], &[*BASEPOINT_G2]),
+            legacy.get_point()
+        );
+
+        //an output built exactly as before the view/spend split, one key pair A and
+        //P = from_bits(r*A)*A, is found and spent by that key imported as an account
+        let legacy_key = get_random_scalar();
+        let legacy_account = Account::from_legacy_key(legacy_key);
+        let public_key = legacy_account.get_spend_public_key();
+        let r = get_random_scalar();
+        let blind_point = RistrettoPoint::multiscalar_mul(&[r], &[*BASEPOINT_G2]);
+        let baseline = OneTimeAccount(RistrettoPoint::multiscalar_mul(
+            &[
+                bytes_to_scalar(&point_to_bytes(&RistrettoPoint::multiscalar_mul(
+                    &[r],
+                    &[public_key],
+                )))
+                .unwrap(),
+            ],
+            &[public_key],
+        ));
+        let view_account = legacy_account.to_view_account();
+        assert!(baseline.belongs_to(&view_account, blind_point, Derivation::Legacy));
+        assert!(!baseline.belongs_to(&view_account, blind_point, Derivation::Indexed(0)));
+        let key = baseline
+            .get_private_key(&legacy_account, blind_point, Derivation::Legacy)
+            .unwrap();
+        let baseline_key = bytes_to_scalar(&point_to_bytes(&RistrettoPoint::multiscalar_mul(
+            &[legacy_key],
+            &[blind_point],
+        )))
+        .unwrap()
+            * legacy_key;
+        assert_eq!(key, baseline_key);
+        assert!(baseline
+            .get_private_key(&account, blind_point, Derivation::Legacy)
+            .is_err());
+
+        for derivation in [Derivation::Legacy, Derivation::Indexed(7)] {
+            let mut writer = Writer::new();
+            derivation.encode(&mut writer);
+            let bytes = writer.into_bytes();
+            let mut reader = Reader::new(&bytes);
+            assert_eq!(Derivation::decode(&mut reader).unwrap(), derivation);
+            reader.finish().unwrap();
+        }
+        assert_eq!(
+            Derivation::decode(&mut Reader::new(&[0])).err(),
+            Some(UtilsError::UnknownTag {
+                kind: "derivation",
+                tag: 0
+            })
+        );
     }
 }
@@ -0,0 +1,227 @@
+use super::{
+    account::gen_one_time_account, account::Derivation, account::OneTimeAccount,
+    error::AccountError, BlindPair,
+};
+use ct_utils::hash::Hasher;
+use ct_utils::{bytes_to_point, point_to_bytes};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use std::fmt;
+use std::str::FromStr;
+
+//layout before base58: network | kind | payload | checksum
+//the kind byte fixes the payload layout, so subaddresses or integrated
+//addresses can be added as new kinds without breaking existing strings.
+//A standard payload is the view public key followed by the spend public key.
+const CHECKSUM_SIZE: usize = 4;
+const PUBLIC_KEY_SIZE: usize = 32;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+impl Network {
+    fn prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x1c,
+            Network::Testnet => 0x3e,
+        }
+    }
+
+    fn from_prefix(prefix: u8) -> Result<Network, AccountError> {
+        match prefix {
+            0x1c => Ok(Network::Mainnet),
+            0x3e => Ok(Network::Testnet),
+            _ => Err(AccountError::UnknownNetwork(prefix)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AddressKind {
+    Standard,
+}
+
+impl AddressKind {
+    fn tag(&self) -> u8 {
+        match self {
+            AddressKind::Standard => 0,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<AddressKind, AccountError> {
+        match tag {
+            0 => Ok(AddressKind::Standard),
+            _ => Err(AccountError::UnknownAddressKind(tag)),
+        }
+    }
+
+    fn payload_size(&self) -> usize {
+        match self {
+            AddressKind::Standard => 2 * PUBLIC_KEY_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Address {
+    network: Network,
+    kind: AddressKind,
+    view_public_key: RistrettoPoint,
+    spend_public_key: RistrettoPoint,
+}
+
+impl Address {
+    pub fn new(
+        network: Network,
+        view_public_key: RistrettoPoint,
+        spend_public_key: RistrettoPoint,
+    ) -> Address {
+        Address {
+            network,
+            kind: AddressKind::Standard,
+            view_public_key,
+            spend_public_key,
+        }
+    }
+
+    pub fn get_network(&self) -> Network {
+        self.network
+    }
+
+    pub fn get_kind(&self) -> AddressKind {
+        self.kind
+    }
+
+    pub fn get_view_public_key(&self) -> RistrettoPoint {
+        self.view_public_key
+    }
+
+    pub fn get_spend_public_key(&self) -> RistrettoPoint {
+        self.spend_public_key
+    }
+
+    //pay to this address without holding the receiver's `Account`
+    pub fn gen_one_time_account(&self, output_index: u32) -> (OneTimeAccount, BlindPair, Vec<u8>) {
+        gen_one_time_account(
+            self.view_public_key,
+            self.spend_public_key,
+            Derivation::Indexed(output_index),
+        )
+    }
+
+    fn checksum(data: &[u8]) -> Vec<u8> {
+        Hasher::sha_256(data)[..CHECKSUM_SIZE].to_vec()
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut data = vec![self.network.prefix(), self.kind.tag()];
+        data.extend(point_to_bytes(&self.view_public_key));
+        data.extend(point_to_bytes(&self.spend_public_key));
+        data.extend(Self::checksum(&data));
+        write!(f, "{}", bs58::encode(data).into_string())
+    }
+}
+
+impl FromStr for Address {
+    type Err = AccountError;
+
+    fn from_str(s: &str) -> Result<Address, AccountError> {
+        let data = bs58::decode(s)
+            .into_vec()
+            .map_err(|_| AccountError::InvalidBase58)?;
+        if data.len() < 2 + CHECKSUM_SIZE {
+            return Err(AccountError::InvalidAddressLength);
+        }
+
+        let (body, checksum) = data.split_at(data.len() - CHECKSUM_SIZE);
+        if Self::checksum(body) != checksum {
+            return Err(AccountError::AddressChecksumMismatch);
+        }
+
+        let network = Network::from_prefix(body[0])?;
+        let kind = AddressKind::from_tag(body[1])?;
+        let payload = &body[2..];
+        if payload.len() != kind.payload_size() {
+            return Err(AccountError::InvalidAddressLength);
+        }
+        let (view, spend) = payload.split_at(PUBLIC_KEY_SIZE);
+        let view_public_key = bytes_to_point(view).map_err(|_| AccountError::InvalidAddressKey)?;
+        let spend_public_key =
+            bytes_to_point(spend).map_err(|_| AccountError::InvalidAddressKey)?;
+
+        Ok(Address {
+            network,
+            kind,
+            view_public_key,
+            spend_public_key,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Address {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Address {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+
+    #[test]
+    fn address_test() {
+        let account = Account::new();
+        let address = account.to_address(Network::Testnet);
+        let text = address.to_string();
+        let parsed: Address = text.parse().unwrap();
+        assert_eq!(parsed, address);
+        assert_eq!(parsed.get_network(), Network::Testnet);
+        assert_ne!(account.to_address(Network::Mainnet).to_string(), text);
+
+        //a payment built from the parsed address is spendable by the account
+        let (one_time_account, blind_pair, _) = parsed.gen_one_time_account(0);
+        assert!(one_time_account
+            .get_private_key(
+                &account,
+                blind_pair.get_blind_point(),
+                Derivation::Indexed(0)
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn address_typo_test() {
+        let text = Account::new().to_address(Network::Mainnet).to_string();
+
+        let mut typo = text.clone().into_bytes();
+        let i = typo.len() / 2;
+        typo[i] = if typo[i] == b'2' { b'3' } else { b'2' };
+        let typo = String::from_utf8(typo).unwrap();
+        assert_eq!(
+            typo.parse::<Address>().unwrap_err(),
+            AccountError::AddressChecksumMismatch
+        );
+
+        let invalid = format!("0{}", &text[1..]);
+        assert_eq!(
+            invalid.parse::<Address>().unwrap_err(),
+            AccountError::InvalidBase58
+        );
+        assert!(text[..text.len() - 1].parse::<Address>().is_err());
+    }
+}
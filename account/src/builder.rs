@@ -0,0 +1,605 @@
+use crate::account::{Account, OneTimeAccount, Recipient};
+use crate::confidential_transaction::{
+    AmountEncoding, ConfidentialTransaction, OutputOptions, RangeProofMode, SignTx,
+};
+use crate::error::AccountError;
+use crate::ring_confidential_transaction::RingSignature;
+use crate::{range_bits, TxHeader, MAX_MEMO_SIZE, TX_VERSION};
+use ct_utils::get_random_index;
+use ct_utils::transcript::ChainId;
+use std::collections::HashSet;
+
+//where ring transactions draw their decoys from, usually the ledger's outputs
+pub trait DecoySource {
+    //`count` ring members spending `size` outputs each. No output is used twice
+    //and none of `exclude` is used at all.
+    fn decoys(
+        &self,
+        count: usize,
+        size: usize,
+        exclude: &[OneTimeAccount],
+    ) -> Result<Vec<Vec<ConfidentialTransaction>>, AccountError>;
+}
+
+impl DecoySource for Vec<ConfidentialTransaction> {
+    fn decoys(
+        &self,
+        count: usize,
+        size: usize,
+        exclude: &[OneTimeAccount],
+    ) -> Result<Vec<Vec<ConfidentialTransaction>>, AccountError> {
+        sample_decoys(self.iter(), count, size, exclude)
+    }
+}
+
+//uniformly samples ring members from `candidates` for a `DecoySource`
+pub fn sample_decoys<'a, I: Iterator<Item = &'a ConfidentialTransaction>>(
+    candidates: I,
+    count: usize,
+    size: usize,
+    exclude: &[OneTimeAccount],
+) -> Result<Vec<Vec<ConfidentialTransaction>>, AccountError> {
+    if size == 0 {
+        return Err(AccountError::NoInputs);
+    }
+    let mut seen: HashSet<_> = exclude.iter().map(|x| x.get_point().compress()).collect();
+    let mut candidates: Vec<&ConfidentialTransaction> = candidates
+        .filter(|x| seen.insert(x.one_time_account.get_point().compress()))
+        .collect();
+    let required = count.saturating_mul(size);
+    if candidates.len() < required {
+        return Err(AccountError::NotEnoughDecoys {
+            available: candidates.len(),
+            required,
+        });
+    }
+
+    let mut picked = Vec::with_capacity(required);
+    while picked.len() < required {
+        let index = get_random_index(candidates.len()).unwrap_or_default();
+        picked.push(candidates.swap_remove(index).clone());
+    }
+    Ok(picked.chunks(size).map(|x| x.to_vec()).collect())
+}
+
+//collects everything a transfer needs and checks all of it before any proof is
+//built. One-time accounts, blinds and the change output are derived internally.
+//The change output comes first, then one output per recipient in order.
+#[derive(Default)]
+pub struct TransactionBuilder<'a> {
+    inputs: Vec<(ConfidentialTransaction, &'a Account)>,
+    recipients: Vec<(&'a dyn Recipient, u64)>,
+    change_to: Option<&'a dyn Recipient>,
+    fee: u64,
+    decoys: Option<(&'a dyn DecoySource, usize)>,
+    memo: Vec<u8>,
+    output_options: OutputOptions,
+    version: Option<u8>,
+    chain_id: ChainId,
+}
+
+impl<'a> TransactionBuilder<'a> {
+    pub fn new() -> TransactionBuilder<'a> {
+        TransactionBuilder::default()
+    }
+
+    //spend `output`, which must belong to `owner`
+    pub fn input(mut self, output: &ConfidentialTransaction, owner: &'a Account) -> Self {
+        self.inputs.push((output.clone(), owner));
+        self
+    }
+
+    pub fn inputs(mut self, outputs: &[ConfidentialTransaction], owner: &'a Account) -> Self {
+        self.inputs
+            .extend(outputs.iter().map(|x| (x.clone(), owner)));
+        self
+    }
+
+    pub fn recipient<R: Recipient>(mut self, to: &'a R, amount: u64) -> Self {
+        self.recipients.push((to, amount));
+        self
+    }
+
+    //defaults to the owner of the first input
+    pub fn change_address<R: Recipient>(mut self, to: &'a R) -> Self {
+        self.change_to = Some(to);
+        self
+    }
+
+    pub fn fee(mut self, fee: u64) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    //only used by `build_ring_signature`, `ring_size` counts the real inputs too
+    pub fn decoys(mut self, source: &'a dyn DecoySource, ring_size: usize) -> Self {
+        self.decoys = Some((source, ring_size));
+        self
+    }
+
+    pub fn memo(mut self, memo: &[u8]) -> Self {
+        self.memo = memo.to_vec();
+        self
+    }
+
+    //`RangeProofMode::Aggregated` shrinks transactions with several outputs
+    pub fn range_proof_mode(mut self, mode: RangeProofMode) -> Self {
+        self.output_options.range_proof_mode = mode;
+        self
+    }
+
+    //`AmountEncoding::Compact` shrinks every output by sending only a masked amount
+    pub fn amount_encoding(mut self, encoding: AmountEncoding) -> Self {
+        self.output_options.amount_encoding = encoding;
+        self
+    }
+
+    //defaults to `TX_VERSION`, which decides how many bits the output amounts may use
+    pub fn version(mut self, version: u8) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    //defaults to `ChainId::MAINNET`, the tx only verifies on this chain
+    pub fn chain_id(mut self, chain_id: ChainId) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    pub fn build_sign_tx(&self) -> Result<SignTx, AccountError> {
+        let checked = self.validate()?;
+        SignTx::sign(
+            &checked.inputs,
+            checked.change_to,
+            &self.recipients,
+            self.fee,
+            self.memo.clone(),
+            self.output_options,
+            self.tx_header(),
+        )
+    }
+
+    pub fn build_ring_signature(&self) -> Result<RingSignature, AccountError> {
+        let Checked {
+            inputs,
+            change_to,
+            change,
+        } = self.validate()?;
+        let (source, ring_size) = self.decoys.ok_or(AccountError::InvalidRingSize(1))?;
+        if ring_size < 2 {
+            return Err(AccountError::InvalidRingSize(ring_size));
+        }
+        let exclude: Vec<OneTimeAccount> = inputs.iter().map(|(x, _)| x.one_time_account).collect();
+        let decoys = source.decoys(ring_size - 1, inputs.len(), &exclude)?;
+        if decoys.len() != ring_size - 1 {
+            return Err(AccountError::RingMemberMismatch);
+        }
+
+        let mut outputs = Vec::with_capacity(self.recipients.len() + 1);
+        outputs.push((change_to, change));
+        outputs.extend_from_slice(&self.recipients);
+        RingSignature::sign(
+            &inputs,
+            &outputs,
+            decoys,
+            self.fee,
+            self.memo.clone(),
+            self.output_options,
+            self.tx_header(),
+        )
+    }
+
+    fn tx_header(&self) -> TxHeader {
+        TxHeader {
+            version: self.version.unwrap_or(TX_VERSION),
+            chain_id: self.chain_id,
+        }
+    }
+
+    fn validate(&self) -> Result<Checked<'_, 'a>, AccountError> {
+        let (_, first_owner) = self.inputs.first().ok_or(AccountError::NoInputs)?;
+        if self.recipients.is_empty() {
+            return Err(AccountError::NoRecipients);
+        }
+        if self.memo.len() > MAX_MEMO_SIZE {
+            return Err(AccountError::MemoTooLong);
+        }
+        let bits = range_bits(self.tx_header().version)?;
+
+        let mut seen = HashSet::with_capacity(self.inputs.len());
+        let mut balance = 0u64;
+        for (input, owner) in &self.inputs {
+            if !seen.insert(input.one_time_account.get_point().compress()) {
+                return Err(AccountError::DuplicateInput);
+            }
+            input
+                .one_time_account
+                .get_private_key(owner, input.blind_point, input.derivation)?;
+            let secret = input.decrypt_token_secrt(owner.get_view_private_key())?;
+            balance = balance
+                .checked_add(secret.balance)
+                .ok_or(AccountError::InsufficientBalance)?;
+        }
+        let change = self
+            .recipients
+            .iter()
+            .try_fold(self.fee, |sum, (_, amount)| sum.checked_add(*amount))
+            .and_then(|spent| balance.checked_sub(spent))
+            .ok_or(AccountError::InsufficientBalance)?;
+        //caught here rather than by the range proof, after the signing material is built
+        if self
+            .recipients
+            .iter()
+            .map(|(_, amount)| *amount)
+            .chain(std::iter::once(change))
+            .any(|x| x > bits.max_amount())
+        {
+            return Err(AccountError::AmountOutOfRange(bits));
+        }
+
+        Ok(Checked {
+            inputs: self.inputs.iter().map(|(x, owner)| (x, *owner)).collect(),
+            change_to: self.change_to.unwrap_or(*first_owner),
+            change,
+        })
+    }
+}
+
+//the inputs paired with their owners, where the change goes and how much it is
+struct Checked<'b, 'a> {
+    inputs: Vec<(&'b ConfidentialTransaction, &'a Account)>,
+    change_to: &'a dyn Recipient,
+    change: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Network;
+    use ct_crypto::error::CryptoError;
+    use ct_token::error::TokenError;
+    use ct_token::prove::RangeBits;
+
+    fn balance(account: &Account, output: &ConfidentialTransaction) -> u64 {
+        output
+            .decrypt_token_secrt(account.get_view_private_key())
+            .unwrap()
+            .balance
+    }
+
+    #[test]
+    fn build_sign_tx_test() {
+        let (alice, bob, carol) = (Account::new(), Account::new(), Account::new());
+        let change_wallet = Account::new();
+        let change_address = change_wallet.to_address(Network::Testnet);
+        let alice_input = ConfidentialTransaction::new(&alice, 50).unwrap();
+        let bob_input = ConfidentialTransaction::new(&bob, 30).unwrap();
+
+        let sign_tx = TransactionBuilder::new()
+            .input(&alice_input, &alice)
+            .input(&bob_input, &bob)
+            .recipient(&carol, 60)
+            .change_address(&change_address)
+            .fee(5)
+            .memo(b"invoice 42")
+            .build_sign_tx()
+            .unwrap();
+        sign_tx.verify().unwrap();
+        assert_eq!(sign_tx.memo, b"invoice 42");
+        assert_eq!(balance(&change_wallet, &sign_tx.outputs[0]), 15);
+        assert_eq!(balance(&carol, &sign_tx.outputs[1]), 60);
+
+        //the memo is signed
+        let mut edited = sign_tx.clone();
+        edited.memo = b"invoice 43".to_vec();
+        assert_eq!(edited.verify(), Err(AccountError::InvalidProof));
+        let decoded = SignTx::from_bytes(&sign_tx.to_bytes()).unwrap();
+        assert_eq!(decoded.memo, sign_tx.memo);
+    }
+
+    #[test]
+    fn build_ring_signature_test() {
+        let (alice, bob) = (Account::new(), Account::new());
+        let inputs = vec![
+            ConfidentialTransaction::new(&alice, 20).unwrap(),
+            ConfidentialTransaction::new(&alice, 25).unwrap(),
+        ];
+        let pool: Vec<ConfidentialTransaction> = (1..=6)
+            .map(|x| ConfidentialTransaction::new(&Account::new(), x).unwrap())
+            .chain(inputs.iter().cloned())
+            .collect();
+
+        let ring_sig = TransactionBuilder::new()
+            .inputs(&inputs, &alice)
+            .recipient(&bob, 40)
+            .fee(1)
+            .decoys(&pool, 3)
+            .memo(b"ring")
+            .build_ring_signature()
+            .unwrap();
+        ring_sig.verify().unwrap();
+        assert_eq!(ring_sig.inputs.len(), 3);
+        assert!(ring_sig.inputs.iter().all(|x| x.len() == 2));
+        assert_eq!(balance(&alice, &ring_sig.outputs[0]), 4);
+        assert_eq!(balance(&bob, &ring_sig.outputs[1]), 40);
+
+        //the real inputs are in the pool too but never picked as decoys
+        let members: HashSet<_> = ring_sig
+            .inputs
+            .iter()
+            .flatten()
+            .map(|x| x.one_time_account.get_point().compress())
+            .collect();
+        assert_eq!(members.len(), 6);
+
+        let mut edited = ring_sig.clone();
+        edited.memo.clear();
+        assert_eq!(edited.verify(), Err(AccountError::InvalidProof));
+        let decoded = RingSignature::from_bytes(&ring_sig.to_bytes()).unwrap();
+        assert_eq!(decoded.memo, b"ring");
+    }
+
+    #[test]
+    fn aggregated_range_proof_test() {
+        let (alice, bob, carol) = (Account::new(), Account::new(), Account::new());
+        let inputs = vec![
+            ConfidentialTransaction::new(&alice, 40).unwrap(),
+            ConfidentialTransaction::new(&alice, 30).unwrap(),
+        ];
+        let pool: Vec<ConfidentialTransaction> = (1..=4)
+            .map(|x| ConfidentialTransaction::new(&Account::new(), x).unwrap())
+            .collect();
+        let builder = || {
+            TransactionBuilder::new()
+                .inputs(&inputs, &alice)
+                .recipient(&bob, 20)
+                .recipient(&carol, 25)
+                .fee(2)
+                .decoys(&pool, 3)
+        };
+
+        //three outputs, padded to four in one proof
+        let per_output = builder().build_sign_tx().unwrap();
+        let sign_tx = builder()
+            .range_proof_mode(RangeProofMode::Aggregated)
+            .build_sign_tx()
+            .unwrap();
+        sign_tx.verify().unwrap();
+        assert!(sign_tx
+            .outputs
+            .iter()
+            .all(|x| x.nonnegative_proof.is_none()));
+        assert_eq!(balance(&alice, &sign_tx.outputs[0]), 23);
+        assert!(sign_tx.to_bytes().len() < per_output.to_bytes().len());
+        let decoded = SignTx::from_bytes(&sign_tx.to_bytes()).unwrap();
+        decoded.verify().unwrap();
+
+        //the aggregate proof is signed and cannot be dropped or mixed
+        let mut edited = sign_tx.clone();
+        edited.range_proof = per_output.range_proof.clone();
+        assert_eq!(edited.verify(), Err(AccountError::RangeProofMismatch));
+        let mut mixed = per_output.clone();
+        mixed.range_proof = sign_tx.range_proof.clone();
+        assert_eq!(mixed.verify(), Err(AccountError::RangeProofMismatch));
+
+        let per_output = builder().build_ring_signature().unwrap();
+        let ring_sig = builder()
+            .range_proof_mode(RangeProofMode::Aggregated)
+            .build_ring_signature()
+            .unwrap();
+        ring_sig.verify().unwrap();
+        assert!(ring_sig.to_bytes().len() < per_output.to_bytes().len());
+        let mut edited = ring_sig.clone();
+        edited.range_proof = None;
+        assert_eq!(edited.verify(), Err(AccountError::InvalidProof));
+        let decoded = RingSignature::from_bytes(&ring_sig.to_bytes()).unwrap();
+        decoded.verify().unwrap();
+    }
+
+    #[test]
+    fn compact_amount_test() {
+        let (alice, bob) = (Account::new(), Account::new());
+        let input = ConfidentialTransaction::new(&alice, 50).unwrap();
+        let pool: Vec<ConfidentialTransaction> = (1..=4)
+            .map(|x| ConfidentialTransaction::new(&Account::new(), x).unwrap())
+            .collect();
+        let builder = || {
+            TransactionBuilder::new()
+                .input(&input, &alice)
+                .recipient(&bob, 20)
+                .fee(1)
+                .decoys(&pool, 3)
+        };
+
+        let sealed = builder().build_sign_tx().unwrap();
+        let sign_tx = builder()
+            .amount_encoding(AmountEncoding::Compact)
+            .build_sign_tx()
+            .unwrap();
+        sign_tx.verify().unwrap();
+        assert_eq!(balance(&alice, &sign_tx.outputs[0]), 29);
+        assert_eq!(balance(&bob, &sign_tx.outputs[1]), 20);
+        //64 bytes saved on each of the two outputs
+        assert_eq!(sign_tx.to_bytes().len() + 128, sealed.to_bytes().len());
+
+        //a compact output is spent like any other
+        let spend = TransactionBuilder::new()
+            .input(&sign_tx.outputs[1], &bob)
+            .recipient(&alice, 15)
+            .decoys(&pool, 3)
+            .build_ring_signature()
+            .unwrap();
+        spend.verify().unwrap();
+        assert_eq!(balance(&bob, &spend.outputs[0]), 5);
+
+        let ring_sig = builder()
+            .amount_encoding(AmountEncoding::Compact)
+            .build_ring_signature()
+            .unwrap();
+        ring_sig.verify().unwrap();
+        assert_eq!(balance(&bob, &ring_sig.outputs[1]), 20);
+        let decoded = RingSignature::from_bytes(&ring_sig.to_bytes()).unwrap();
+        decoded.verify().unwrap();
+        assert_eq!(balance(&bob, &decoded.outputs[1]), 20);
+
+        //an edited masked amount no longer opens the commitment
+        let mut edited = sign_tx.outputs[1].clone();
+        edited.encrypto_token_secret = sign_tx.outputs[0].encrypto_token_secret.clone();
+        assert_eq!(
+            edited.decrypt_token_secrt(bob.get_view_private_key()).err(),
+            Some(AccountError::Token(TokenError::Crypto(
+                CryptoError::AuthenticationFailed
+            )))
+        );
+    }
+
+    #[test]
+    fn version_test() {
+        let (alice, bob) = (Account::new(), Account::new());
+        let input = ConfidentialTransaction::new(&alice, 1 << 40).unwrap();
+        let builder = || {
+            TransactionBuilder::new()
+                .input(&input, &alice)
+                .recipient(&bob, 1 << 36)
+        };
+
+        //amounts above 32 bits need version 2
+        let sign_tx = builder().build_sign_tx().unwrap();
+        assert_eq!(sign_tx.version, TX_VERSION);
+        sign_tx.verify().unwrap();
+        assert_eq!(balance(&bob, &sign_tx.outputs[1]), 1 << 36);
+        assert_eq!(
+            builder().version(1).build_sign_tx().err(),
+            Some(AccountError::AmountOutOfRange(RangeBits::Bits32))
+        );
+        //the change counts as an output too
+        assert_eq!(
+            TransactionBuilder::new()
+                .input(&input, &alice)
+                .recipient(&bob, 1)
+                .version(1)
+                .build_ring_signature()
+                .err(),
+            Some(AccountError::AmountOutOfRange(RangeBits::Bits32))
+        );
+        assert_eq!(
+            builder().version(0).build_sign_tx().err(),
+            Some(AccountError::UnsupportedTxVersion(0))
+        );
+
+        //a version 1 tx still verifies under the width it was proven for
+        let small = ConfidentialTransaction::new(&alice, 90).unwrap();
+        let sign_tx = TransactionBuilder::new()
+            .input(&small, &alice)
+            .recipient(&bob, 80)
+            .version(1)
+            .range_proof_mode(RangeProofMode::Aggregated)
+            .build_sign_tx()
+            .unwrap();
+        sign_tx.verify().unwrap();
+        let mut edited = sign_tx.clone();
+        edited.version = 3;
+        assert_eq!(edited.verify(), Err(AccountError::UnsupportedTxVersion(3)));
+    }
+
+    #[test]
+    fn chain_id_test() {
+        let (alice, bob) = (Account::new(), Account::new());
+        let input = ConfidentialTransaction::new(&alice, 90).unwrap();
+        let pool: Vec<ConfidentialTransaction> = (1..=3)
+            .map(|x| ConfidentialTransaction::new(&Account::new(), x).unwrap())
+            .collect();
+        let builder = || {
+            TransactionBuilder::new()
+                .input(&input, &alice)
+                .recipient(&bob, 80)
+                .decoys(&pool, 2)
+                .chain_id(ChainId::TESTNET)
+        };
+
+        let sign_tx = builder().build_sign_tx().unwrap();
+        sign_tx.verify().unwrap();
+        let decoded = SignTx::from_bytes(&sign_tx.to_bytes()).unwrap();
+        assert_eq!(decoded.chain_id, ChainId::TESTNET);
+
+        //re-signing for another chain does not help, the proofs are bound to it
+        let mut moved = sign_tx.clone();
+        moved.chain_id = ChainId::MAINNET;
+        let key = input
+            .one_time_account
+            .get_private_key(&alice, input.blind_point, input.derivation)
+            .unwrap();
+        (moved.sign_msgs, moved.key_image_proof) =
+            SignTx::sign_inputs(&[key], &moved.inputs, &moved.prefix_hash()).unwrap();
+        assert_eq!(moved.verify(), Err(AccountError::InvalidProof));
+
+        let ring_sig = builder().build_ring_signature().unwrap();
+        ring_sig.verify().unwrap();
+        let mut moved = ring_sig.clone();
+        moved.chain_id = ChainId::MAINNET;
+        assert_eq!(moved.verify(), Err(AccountError::InvalidProof));
+    }
+
+    #[test]
+    fn builder_error_test() {
+        let (alice, bob) = (Account::new(), Account::new());
+        let input = ConfidentialTransaction::new(&alice, 10).unwrap();
+        let pool = vec![ConfidentialTransaction::new(&bob, 3).unwrap()];
+        let builder = || TransactionBuilder::new().input(&input, &alice);
+
+        assert_eq!(
+            TransactionBuilder::new()
+                .recipient(&bob, 1)
+                .build_sign_tx()
+                .err(),
+            Some(AccountError::NoInputs)
+        );
+        assert_eq!(
+            builder().build_sign_tx().err(),
+            Some(AccountError::NoRecipients)
+        );
+        assert_eq!(
+            builder()
+                .input(&input, &alice)
+                .recipient(&bob, 1)
+                .build_sign_tx()
+                .err(),
+            Some(AccountError::DuplicateInput)
+        );
+        assert_eq!(
+            TransactionBuilder::new()
+                .input(&input, &bob)
+                .recipient(&bob, 1)
+                .build_sign_tx()
+                .err(),
+            Some(AccountError::NotOwner)
+        );
+        assert_eq!(
+            builder().recipient(&bob, 8).fee(3).build_sign_tx().err(),
+            Some(AccountError::InsufficientBalance)
+        );
+        assert_eq!(
+            builder()
+                .recipient(&bob, 1)
+                .memo(&[0; MAX_MEMO_SIZE + 1])
+                .build_sign_tx()
+                .err(),
+            Some(AccountError::MemoTooLong)
+        );
+        assert_eq!(
+            builder().recipient(&bob, 1).build_ring_signature().err(),
+            Some(AccountError::InvalidRingSize(1))
+        );
+        assert_eq!(
+            builder()
+                .recipient(&bob, 1)
+                .decoys(&pool, 3)
+                .build_ring_signature()
+                .err(),
+            Some(AccountError::NotEnoughDecoys {
+                available: 1,
+                required: 2,
+            })
+        );
+    }
+}
@@ -1,142 +1,797 @@
-use super::{account::Account, account::OneTimeAccount};
+use super::{
+    account::Account, account::Derivation, account::OneTimeAccount, account::Recipient,
+    error::AccountError, range_bits, TxHash, TxHeader, MAX_MEMO_SIZE, TX_VERSION,
+};
 use ct_crypto::signature::{self, SignMsg};
-use ct_token::prove::{NonnegativeProof, SumProof};
+use ct_mlsag::{material::KeyPair, mlsag::Mlsag, signature::Signarute};
+use ct_token::batch::BatchVerifier;
+use ct_token::prove::{AggregateRangeProof, NonnegativeProof, RangeBits, SumProof};
 use ct_token::token::{EncryptoTokenSecret, Token, TokenSecret};
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
 use ct_utils::hash::Hasher;
 use ct_utils::point_to_bytes;
+use ct_utils::transcript::{ChainId, Label, TxContext};
 use curve25519_dalek::traits::MultiscalarMul;
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
+use std::collections::HashSet;
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConfidentialTransaction {
     pub one_time_account: OneTimeAccount,
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
     pub blind_point: RistrettoPoint,
+    //which derivation `one_time_account` was built with
+    pub derivation: Derivation,
     pub token: Token,
-    pub nonnegative_proof: NonnegativeProof,
+    //`None` when the transaction carries one `AggregateRangeProof` for all outputs
+    pub nonnegative_proof: Option<NonnegativeProof>,
     pub encrypto_token_secret: EncryptoTokenSecret,
 }
 
 impl ConfidentialTransaction {
+    //a fresh output paying `amount` to `recipient`, e.g. a genesis allocation
+    pub fn new<R: Recipient + ?Sized>(
+        recipient: &R,
+        amount: u64,
+    ) -> Result<ConfidentialTransaction, AccountError> {
+        let bits = range_bits(TX_VERSION)?;
+        let context = TxContext::default();
+        Ok(Self::new_with_secret(
+            recipient,
+            amount,
+            0,
+            OutputOptions::default(),
+            bits,
+            &context,
+        )?
+        .0)
+    }
+
+    //the output at `output_index` of its transaction, its range proof is bound to
+    //`context`. In `RangeProofMode::Aggregated` it has no proof of its own.
+    pub(crate) fn new_with_secret<R: Recipient + ?Sized>(
+        recipient: &R,
+        amount: u64,
+        output_index: u32,
+        options: OutputOptions,
+        bits: RangeBits,
+        context: &TxContext,
+    ) -> Result<(ConfidentialTransaction, TokenSecret), AccountError> {
+        let (one_time_account, blind_pair, symmetric_key) =
+            recipient.gen_one_time_account(output_index);
+        let (token, token_secrt) = match options.amount_encoding {
+            AmountEncoding::Sealed => Token::mint(amount),
+            AmountEncoding::Compact => Token::mint_compact(amount, &symmetric_key),
+        };
+        let nonnegative_proof = match options.range_proof_mode {
+            RangeProofMode::PerOutput => Some(NonnegativeProof::new(&token_secrt, bits, context)?),
+            RangeProofMode::Aggregated => None,
+        };
+        let encrypto_token_secret = match options.amount_encoding {
+            AmountEncoding::Sealed => {
+                token_secrt.encrypt(&symmetric_key, &one_time_account.get_point())?
+            }
+            AmountEncoding::Compact => token_secrt.mask(&symmetric_key),
+        };
+        let output = ConfidentialTransaction {
+            one_time_account,
+            blind_point: blind_pair.get_blind_point(),
+            derivation: Derivation::Indexed(output_index),
+            token,
+            nonnegative_proof,
+            encrypto_token_secret,
+        };
+        Ok((output, token_secrt))
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes1 = point_to_bytes(&self.one_time_account.get_point());
-        let mut bytes2 = point_to_bytes(&self.blind_point);
-        let mut bytes3 = point_to_bytes(&self.token.get_point());
-        bytes1.append(&mut bytes2);
-        bytes1.append(&mut bytes3);
-        bytes1
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ConfidentialTransaction, AccountError> {
+        Ok(codec::from_bytes(bytes)?)
     }
 
     //the ECDH algorithm is used to generate symmetric key
-    //then use symmetric key to decrypt "encrypto_token_secret"
-    pub fn decrypt_token_secrt(&self, key: Scalar) -> Result<TokenSecret, &'static str> {
+    //then use symmetric key to decrypt "encrypto_token_secret",
+    //only the receiver's view key is needed. The secret is bound to this output,
+    //a wrong key or a tampered output fails with `CryptoError::AuthenticationFailed`
+    pub fn decrypt_token_secrt(&self, view_key: Scalar) -> Result<TokenSecret, AccountError> {
         let symmetric_key = Hasher::sha_256(&point_to_bytes(&RistrettoPoint::multiscalar_mul(
-            &[key],
+            &[view_key],
             &[self.blind_point],
         )));
 
-        self.encrypto_token_secret.decrypt(&symmetric_key)
+        Ok(self.encrypto_token_secret.decrypt(
+            &symmetric_key,
+            &self.one_time_account.get_point(),
+            &self.token,
+        )?)
     }
 
-    pub fn transfer(
+    //single input transfer, see `SignTx::new`
+    pub fn transfer<R: Recipient>(
         &self,
         from: &Account,
-        to: &Account,
+        to: &R,
         amount: u64,
-    ) -> Result<SignTx, &'static str> {
-        let key = self
-            .one_time_account
-            .get_private_key(from, self.blind_point)?;
-        let input_token_secrt = self.decrypt_token_secrt(key)?;
-
-        let (output1_one_time_account, output1_blind_pair, output1_symmetric_key) =
-            from.gen_one_time_account();
-        let (output2_one_time_account, output2_blind_pair, output2_symmetric_key) =
-            to.gen_one_time_account();
-
-        let (output1_token, output1_token_secret) = Token::mint(input_token_secrt.balance - amount);
-        let (output2_token, output2_token_secret) = Token::mint(amount);
+        fee: u64,
+    ) -> Result<SignTx, AccountError> {
+        SignTx::new(std::slice::from_ref(self), from, to, amount, fee)
+    }
+}
 
-        let output1_crypt_secret = output1_token_secret.encrypt(&output1_symmetric_key)?;
-        let output2_crypt_secret = output2_token_secret.encrypt(&output2_symmetric_key)?;
+impl Encode for ConfidentialTransaction {
+    fn encode(&self, writer: &mut Writer) {
+        self.one_time_account.encode(writer);
+        self.blind_point.encode(writer);
+        self.derivation.encode(writer);
+        self.token.encode(writer);
+        self.nonnegative_proof.encode(writer);
+        self.encrypto_token_secret.encode(writer);
+    }
+}
 
-        let output1_onnegative_proof = NonnegativeProof::new(&output1_token_secret);
-        let output2_onnegative_proof = NonnegativeProof::new(&output2_token_secret);
+impl Decode for ConfidentialTransaction {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(ConfidentialTransaction {
+            one_time_account: OneTimeAccount::decode(reader)?,
+            blind_point: RistrettoPoint::decode(reader)?,
+            derivation: Derivation::decode(reader)?,
+            token: Token::decode(reader)?,
+            nonnegative_proof: Option::decode(reader)?,
+            encrypto_token_secret: EncryptoTokenSecret::decode(reader)?,
+        })
+    }
+}
 
-        let output1_ct = ConfidentialTransaction {
-            one_time_account: output1_one_time_account,
-            blind_point: output1_blind_pair.get_blind_point(),
-            token: output1_token,
-            nonnegative_proof: output1_onnegative_proof,
-            encrypto_token_secret: output1_crypt_secret,
-        };
+//how a transaction proves its output amounts are in range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RangeProofMode {
+    //a `NonnegativeProof` in every output
+    #[default]
+    PerOutput,
+    //one `AggregateRangeProof` on the transaction, logarithmic in the output count
+    Aggregated,
+}
 
-        let output2_ct = ConfidentialTransaction {
-            one_time_account: output2_one_time_account,
-            blind_point: output2_blind_pair.get_blind_point(),
-            token: output2_token,
-            nonnegative_proof: output2_onnegative_proof,
-            encrypto_token_secret: output2_crypt_secret,
-        };
+//how an output carries its amount and blind to the recipient
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AmountEncoding {
+    //both encrypted with AES-GCM, see `EncryptoTokenSecret::Sealed`
+    #[default]
+    Sealed,
+    //the blind is derived from the ECDH secret and only an 8-byte masked amount
+    //is sent, see `EncryptoTokenSecret::Compact`
+    Compact,
+}
 
-        let outputs = vec![output1_ct, output2_ct];
-        let mut outputs_bytes = outputs.iter().map(|x| x.to_bytes()).flatten().collect();
-        let sign_msg = signature::sign(key, &mut outputs_bytes);
+//the choices a sender makes about the outputs of a transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct OutputOptions {
+    pub range_proof_mode: RangeProofMode,
+    pub amount_encoding: AmountEncoding,
+}
 
-        let sum_proof = SumProof::new_sum_proof(
-            &input_token_secrt,
-            &output1_token_secret,
-            &output2_token_secret,
-        );
+//the outputs of a transfer, their secrets and, in aggregated mode, the range
+//proof covering all of them
+pub(crate) struct NewOutputs {
+    pub outputs: Vec<ConfidentialTransaction>,
+    pub secrets: Vec<TokenSecret>,
+    pub range_proof: Option<AggregateRangeProof>,
+}
 
-        let sign_tx = SignTx {
-            input: self.clone(),
-            outputs: outputs,
-            sign_msg: sign_msg,
-            sum_proof: sum_proof,
-        };
+pub(crate) fn new_outputs(
+    payments: &[(&dyn Recipient, u64)],
+    options: OutputOptions,
+    bits: RangeBits,
+    context: &TxContext,
+) -> Result<NewOutputs, AccountError> {
+    let (outputs, secrets): (Vec<_>, Vec<_>) = payments
+        .iter()
+        .enumerate()
+        .map(|(i, (recipient, amount))| {
+            ConfidentialTransaction::new_with_secret(
+                *recipient, *amount, i as u32, options, bits, context,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .unzip();
+    let range_proof = match options.range_proof_mode {
+        RangeProofMode::PerOutput => None,
+        RangeProofMode::Aggregated => Some(AggregateRangeProof::new(&secrets, bits, context)?),
+    };
+    Ok(NewOutputs {
+        outputs,
+        secrets,
+        range_proof,
+    })
+}
 
-        Ok(sign_tx)
+//either one aggregate proof covers all outputs and none has its own, or every
+//output carries a proof of its own. Range proofs cannot go into a `BatchVerifier`
+//and are verified right away.
+pub(crate) fn verify_range_proofs(
+    outputs: &[ConfidentialTransaction],
+    range_proof: Option<&AggregateRangeProof>,
+    bits: RangeBits,
+    context: &TxContext,
+) -> Result<(), AccountError> {
+    match range_proof {
+        Some(proof) => {
+            if outputs.iter().any(|x| x.nonnegative_proof.is_some()) {
+                return Err(AccountError::RangeProofMismatch);
+            }
+            let tokens: Vec<Token> = outputs.iter().map(|x| x.token).collect();
+            if !proof.verify(&tokens, bits, context) {
+                return Err(AccountError::InvalidProof);
+            }
+        }
+        None => {
+            if outputs.iter().any(|x| x.nonnegative_proof.is_none()) {
+                return Err(AccountError::RangeProofMismatch);
+            }
+            for x in outputs.iter() {
+                if let Some(proof) = &x.nonnegative_proof {
+                    if !proof.verify(&x.token, bits, context) {
+                        return Err(AccountError::InvalidProof);
+                    }
+                }
+            }
+        }
     }
+    Ok(())
 }
 
+//every input is signed with its own one-time key over the same prefix hash
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SignTx {
-    pub input: ConfidentialTransaction,
+    //the protocol rules the tx was built under, see `range_bits`
+    pub version: u8,
+    //the chain the tx is valid on
+    pub chain_id: ChainId,
+    pub inputs: Vec<ConfidentialTransaction>,
     pub outputs: Vec<ConfidentialTransaction>,
-    pub sign_msg: SignMsg,
+    //covers all outputs, see `RangeProofMode::Aggregated`
+    pub range_proof: Option<AggregateRangeProof>,
+    pub fee: u64,
+    //public, at most `MAX_MEMO_SIZE` bytes
+    pub memo: Vec<u8>,
+    pub sign_msgs: Vec<SignMsg>,
+    //an MLSAG whose ring is just the inputs, its key images are burned like those
+    //of a ring spend so an output cannot be spent once each way
+    pub key_image_proof: Signarute,
     pub sum_proof: SumProof,
 }
 
 impl SignTx {
-    pub fn verify(&mut self) -> Result<bool, &'static str> {
-        //verify signature
-        let mut outputs_bytes = self
-            .outputs
+    const PREFIX_DOMAIN: &'static [u8] = b"ct_sign_tx_prefix";
+    const TXID_DOMAIN: &'static [u8] = b"ct_sign_tx_id";
+    const INPUT_LABEL: Label = Label::new(b"sign_tx_input");
+
+    //spends all of `inputs`, which must belong to `from`. `to` gets `amount`, `fee`
+    //goes to the block producer and the change output back to `from` gets the rest.
+    pub fn new<R: Recipient>(
+        inputs: &[ConfidentialTransaction],
+        from: &Account,
+        to: &R,
+        amount: u64,
+        fee: u64,
+    ) -> Result<SignTx, AccountError> {
+        SignTx::pay(inputs, from, &[(to as &dyn Recipient, amount)], fee)
+    }
+
+    //like `new` with any number of payments. The change output comes first, then
+    //one output per payment in order.
+    pub fn pay(
+        inputs: &[ConfidentialTransaction],
+        from: &Account,
+        payments: &[(&dyn Recipient, u64)],
+        fee: u64,
+    ) -> Result<SignTx, AccountError> {
+        let inputs: Vec<(&ConfidentialTransaction, &Account)> =
+            inputs.iter().map(|x| (x, from)).collect();
+        SignTx::sign(
+            &inputs,
+            from,
+            payments,
+            fee,
+            Vec::new(),
+            OutputOptions::default(),
+            TxHeader::default(),
+        )
+    }
+
+    //each input is opened and signed with the keys of its own owner, the change
+    //goes to `change_to`
+    pub(crate) fn sign(
+        inputs: &[(&ConfidentialTransaction, &Account)],
+        change_to: &dyn Recipient,
+        payments: &[(&dyn Recipient, u64)],
+        fee: u64,
+        memo: Vec<u8>,
+        options: OutputOptions,
+        header: TxHeader,
+    ) -> Result<SignTx, AccountError> {
+        let bits = range_bits(header.version)?;
+        if inputs.is_empty() {
+            return Err(AccountError::NoInputs);
+        }
+        if memo.len() > MAX_MEMO_SIZE {
+            return Err(AccountError::MemoTooLong);
+        }
+        let keys = inputs
+            .iter()
+            .map(|(x, owner)| {
+                x.one_time_account
+                    .get_private_key(owner, x.blind_point, x.derivation)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let input_token_secrts = inputs
+            .iter()
+            .map(|(x, owner)| x.decrypt_token_secrt(owner.get_view_private_key()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let change = input_token_secrts
+            .iter()
+            .try_fold(0u64, |sum, x| sum.checked_add(x.balance))
+            .zip(
+                payments
+                    .iter()
+                    .try_fold(fee, |sum, (_, amount)| sum.checked_add(*amount)),
+            )
+            .and_then(|(balance, spent)| balance.checked_sub(spent))
+            .ok_or(AccountError::InsufficientBalance)?;
+
+        let inputs: Vec<ConfidentialTransaction> =
+            inputs.iter().map(|(x, _)| (*x).clone()).collect();
+        let context = SignTx::proof_context(header, &inputs);
+        let mut payments = payments.to_vec();
+        payments.insert(0, (change_to, change));
+        let NewOutputs {
+            outputs,
+            secrets: output_token_secrts,
+            range_proof,
+        } = new_outputs(&payments, options, bits, &context)?;
+        let sum_proof =
+            SumProof::new_sum_proof(&input_token_secrts, &output_token_secrts, fee, &context)?;
+
+        let prefix_hash = SignTx::compute_prefix_hash(
+            header,
+            &inputs,
+            &outputs,
+            &range_proof,
+            fee,
+            &memo,
+            &sum_proof,
+        );
+        let (sign_msgs, key_image_proof) = SignTx::sign_inputs(&keys, &inputs, &prefix_hash)?;
+
+        Ok(SignTx {
+            version: header.version,
+            chain_id: header.chain_id,
+            inputs,
+            outputs,
+            range_proof,
+            fee,
+            memo,
+            sign_msgs,
+            key_image_proof,
+            sum_proof,
+        })
+    }
+
+    //the signature of every input over `prefix_hash` and the proof of their key images
+    pub(crate) fn sign_inputs(
+        keys: &[Scalar],
+        inputs: &[ConfidentialTransaction],
+        prefix_hash: &TxHash,
+    ) -> Result<(Vec<SignMsg>, Signarute), AccountError> {
+        let sign_msgs = keys
             .iter()
-            .map(|x| x.to_bytes())
-            .flatten()
+            .map(|key| signature::sign(*key, prefix_hash))
             .collect();
-        if !signature::verify(
-            self.input.one_time_account.get_point(),
-            &self.sign_msg,
-            &mut outputs_bytes,
-        ) {
-            return Ok(false);
+        let mut mlsag = Mlsag::default();
+        mlsag.add_signer(
+            keys.iter()
+                .zip(inputs.iter())
+                .map(|(key, x)| KeyPair {
+                    private_key: *key,
+                    public_key: x.one_time_account.get_point(),
+                })
+                .collect(),
+        );
+        Ok((sign_msgs, mlsag.sign_at(prefix_hash, 0)?))
+    }
+
+    //one per input, in input order
+    pub fn key_images(&self) -> &[RistrettoPoint] {
+        &self.key_image_proof.key_images
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SignTx, AccountError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
+
+    pub fn header(&self) -> TxHeader {
+        TxHeader {
+            version: self.version,
+            chain_id: self.chain_id,
         }
+    }
+
+    //hash of everything except the signature, this is the signed message
+    pub fn prefix_hash(&self) -> TxHash {
+        Self::compute_prefix_hash(
+            self.header(),
+            &self.inputs,
+            &self.outputs,
+            &self.range_proof,
+            self.fee,
+            &self.memo,
+            &self.sum_proof,
+        )
+    }
+
+    //hash of the full canonical encoding, signature included
+    pub fn txid(&self) -> TxHash {
+        Hasher::domain_sha_256(Self::TXID_DOMAIN, &self.to_bytes())
+    }
+
+    //the proofs are bound to the spent one-time accounts, which no other
+    //transaction on the chain can spend again
+    fn proof_context(header: TxHeader, inputs: &[ConfidentialTransaction]) -> TxContext {
+        inputs.iter().fold(header.proof_context(), |context, x| {
+            context.bind(
+                Self::INPUT_LABEL,
+                &point_to_bytes(&x.one_time_account.get_point()),
+            )
+        })
+    }
 
-        //verify nonnegative proof
+    fn compute_prefix_hash(
+        header: TxHeader,
+        inputs: &[ConfidentialTransaction],
+        outputs: &[ConfidentialTransaction],
+        range_proof: &Option<AggregateRangeProof>,
+        fee: u64,
+        memo: &[u8],
+        sum_proof: &SumProof,
+    ) -> TxHash {
+        let mut writer = Writer::new();
+        writer.write_u8(codec::WIRE_VERSION);
+        writer.write_u8(header.version);
+        header.chain_id.encode(&mut writer);
+        inputs.encode(&mut writer);
+        outputs.encode(&mut writer);
+        range_proof.encode(&mut writer);
+        writer.write_u64(fee);
+        writer.write_bytes(memo);
+        sum_proof.encode(&mut writer);
+        Hasher::domain_sha_256(Self::PREFIX_DOMAIN, &writer.into_bytes())
+    }
+
+    pub fn verify(&self) -> Result<(), AccountError> {
+        let mut batch = BatchVerifier::new();
+        self.add_to_batch(&mut batch)?;
+        batch.verify().map_err(|_| AccountError::InvalidProof)
+    }
+
+    //queues the signatures and sum proof so that many transactions can be verified
+    //at once. The range proofs and the key image proof cannot be batched and are
+    //checked right away.
+    pub fn add_to_batch<'a>(&'a self, batch: &mut BatchVerifier<'a>) -> Result<(), AccountError> {
+        let bits = range_bits(self.version)?;
+        if self.inputs.is_empty() {
+            return Err(AccountError::NoInputs);
+        }
+        if self.memo.len() > MAX_MEMO_SIZE {
+            return Err(AccountError::MemoTooLong);
+        }
+        if self.sign_msgs.len() != self.inputs.len() || self.key_images().len() != self.inputs.len()
+        {
+            return Err(AccountError::SignatureCountMismatch);
+        }
+        //an input listed twice would be counted twice in the sum proof
+        let mut seen = HashSet::with_capacity(self.inputs.len());
         if !self
-            .outputs
-            .iter_mut()
-            .all(|x| x.nonnegative_proof.verify(&x.token))
+            .inputs
+            .iter()
+            .all(|x| seen.insert(x.one_time_account.get_point().compress()))
         {
-            return Ok(false);
+            return Err(AccountError::DuplicateInput);
         }
 
-        //verify sum proof
-        self.sum_proof.verify_sum_proof(
-            &self.input.token,
-            &self.outputs[0].token,
-            &self.outputs[1].token,
-        )
+        //the sum proof also rejects outputs added or dropped after proving
+        let input_tokens: Vec<Token> = self.inputs.iter().map(|x| x.token).collect();
+        let output_tokens: Vec<Token> = self.outputs.iter().map(|x| x.token).collect();
+        let context = SignTx::proof_context(self.header(), &self.inputs);
+        batch.add_sum_proof(
+            &self.sum_proof,
+            &input_tokens,
+            &output_tokens,
+            self.fee,
+            &context,
+        )?;
+        verify_range_proofs(&self.outputs, self.range_proof.as_ref(), bits, &context)?;
+
+        let prefix_hash = self.prefix_hash();
+        let ring = vec![self
+            .inputs
+            .iter()
+            .map(|x| x.one_time_account.get_point())
+            .collect::<Vec<_>>()];
+        if !self.key_image_proof.verify(&prefix_hash, &ring) {
+            return Err(AccountError::InvalidProof);
+        }
+        for (x, y) in self.inputs.iter().zip(self.sign_msgs.iter()) {
+            batch.add_signature(x.one_time_account.get_point(), y, &prefix_hash);
+        }
+        Ok(())
+    }
+}
+
+impl Encode for SignTx {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u8(self.version);
+        self.chain_id.encode(writer);
+        self.inputs.encode(writer);
+        self.outputs.encode(writer);
+        self.range_proof.encode(writer);
+        writer.write_u64(self.fee);
+        writer.write_bytes(&self.memo);
+        self.sign_msgs.encode(writer);
+        self.key_image_proof.encode(writer);
+        self.sum_proof.encode(writer);
+    }
+}
+
+impl Decode for SignTx {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(SignTx {
+            version: reader.read_u8()?,
+            chain_id: ChainId::decode(reader)?,
+            inputs: Vec::decode(reader)?,
+            outputs: Vec::decode(reader)?,
+            range_proof: Option::decode(reader)?,
+            fee: reader.read_u64()?,
+            memo: reader.read_bytes()?,
+            sign_msgs: Vec::decode(reader)?,
+            key_image_proof: Signarute::decode(reader)?,
+            sum_proof: SumProof::decode(reader)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ct_crypto::error::CryptoError;
+    use ct_token::error::TokenError;
+
+    fn mint(account: &Account, amount: u64) -> ConfidentialTransaction {
+        ConfidentialTransaction::new(account, amount).unwrap()
+    }
+
+    #[test]
+    fn sign_tx_bytes_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let sign_tx = mint(&from, 100).transfer(&from, &to, 40, 0).unwrap();
+
+        let bytes = sign_tx.to_bytes();
+        let decoded = SignTx::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        decoded.verify().unwrap();
+
+        assert!(SignTx::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert!(SignTx::from_bytes(&extended).is_err());
+    }
+
+    #[test]
+    fn sign_tx_txid_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let input = mint(&from, 100);
+        let sign_tx = input.transfer(&from, &to, 40, 0).unwrap();
+        assert_eq!(
+            sign_tx.txid(),
+            SignTx::from_bytes(&sign_tx.to_bytes()).unwrap().txid()
+        );
+        assert_ne!(sign_tx.txid(), sign_tx.prefix_hash());
+
+        //a different signature changes the txid but not the prefix
+        let mut resigned = SignTx::from_bytes(&sign_tx.to_bytes()).unwrap();
+        let key = input
+            .one_time_account
+            .get_private_key(&from, input.blind_point, input.derivation)
+            .unwrap();
+        resigned.sign_msgs[0] = signature::sign(key, &resigned.prefix_hash());
+        resigned.verify().unwrap();
+        assert_eq!(resigned.prefix_hash(), sign_tx.prefix_hash());
+        assert_ne!(resigned.txid(), sign_tx.txid());
+
+        //swapping outputs changes both
+        let mut swapped = SignTx::from_bytes(&sign_tx.to_bytes()).unwrap();
+        swapped.outputs.swap(0, 1);
+        assert_ne!(swapped.prefix_hash(), sign_tx.prefix_hash());
+        assert_ne!(swapped.txid(), sign_tx.txid());
+        assert_eq!(swapped.verify(), Err(AccountError::InvalidProof));
+    }
+
+    #[test]
+    fn sign_tx_fee_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let input = mint(&from, 100);
+        let sign_tx = input.transfer(&from, &to, 40, 7).unwrap();
+        sign_tx.verify().unwrap();
+        let change = sign_tx.outputs[0]
+            .decrypt_token_secrt(from.get_view_private_key())
+            .unwrap();
+        assert_eq!(change.balance, 53);
+
+        //the fee is signed, raising it invalidates the signature
+        let mut raised = sign_tx.clone();
+        raised.fee = 8;
+        assert_eq!(raised.verify(), Err(AccountError::InvalidProof));
+
+        assert_eq!(
+            input.transfer(&from, &to, 95, 6).err(),
+            Some(AccountError::InsufficientBalance)
+        );
+    }
+
+    #[test]
+    fn decrypt_token_secrt_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let output = mint(&from, 100);
+        assert_eq!(
+            output
+                .decrypt_token_secrt(from.get_view_private_key())
+                .unwrap()
+                .balance,
+            100
+        );
+
+        let failed = Some(AccountError::Token(TokenError::Crypto(
+            CryptoError::AuthenticationFailed,
+        )));
+        assert_eq!(
+            output.decrypt_token_secrt(to.get_view_private_key()).err(),
+            failed
+        );
+
+        //a secret moved onto another commitment no longer decrypts
+        let mut moved = output.clone();
+        moved.token = mint(&from, 100).token;
+        assert_eq!(
+            moved.decrypt_token_secrt(from.get_view_private_key()).err(),
+            failed
+        );
+    }
+
+    #[test]
+    fn sign_tx_multi_input_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let inputs = vec![mint(&from, 30), mint(&from, 50)];
+        let sign_tx = SignTx::new(&inputs, &from, &to, 70, 2).unwrap();
+        sign_tx.verify().unwrap();
+        assert_eq!(sign_tx.sign_msgs.len(), 2);
+        let change = sign_tx.outputs[0]
+            .decrypt_token_secrt(from.get_view_private_key())
+            .unwrap();
+        assert_eq!(change.balance, 8);
+
+        //a signature is needed from every input
+        let mut unsigned = sign_tx.clone();
+        unsigned.sign_msgs.pop();
+        assert_eq!(unsigned.verify(), Err(AccountError::SignatureCountMismatch));
+
+        //each input burns the key image a ring spend of it would show
+        let key_images: Vec<RistrettoPoint> = inputs
+            .iter()
+            .map(|x| {
+                KeyPair {
+                    public_key: x.one_time_account.get_point(),
+                    private_key: x
+                        .one_time_account
+                        .get_private_key(&from, x.blind_point, x.derivation)
+                        .unwrap(),
+                }
+                .compute_key_images()
+            })
+            .collect();
+        assert_eq!(sign_tx.key_images(), key_images);
+        let mut unlinked = sign_tx.clone();
+        unlinked.key_image_proof = SignTx::new(&inputs, &from, &to, 60, 2)
+            .unwrap()
+            .key_image_proof;
+        assert_eq!(unlinked.verify(), Err(AccountError::InvalidProof));
+        unlinked.key_image_proof.key_images.pop();
+        assert_eq!(unlinked.verify(), Err(AccountError::SignatureCountMismatch));
+
+        //listing an input twice would count its value twice
+        let doubled = vec![inputs[1].clone(), inputs[1].clone()];
+        let sign_tx = SignTx::new(&doubled, &from, &to, 100, 0).unwrap();
+        assert_eq!(sign_tx.verify(), Err(AccountError::DuplicateInput));
+
+        assert_eq!(
+            SignTx::new(&inputs, &from, &to, 80, 1).err(),
+            Some(AccountError::InsufficientBalance)
+        );
+        assert_eq!(
+            SignTx::new(&inputs, &to, &from, 10, 0).err(),
+            Some(AccountError::NotOwner)
+        );
+    }
+
+    #[test]
+    fn sign_tx_many_outputs_test() {
+        let (from, alice, bob) = (Account::new(), Account::new(), Account::new());
+        let inputs = vec![mint(&from, 60), mint(&from, 45)];
+        let payments: [(&dyn Recipient, u64); 3] = [(&alice, 20), (&bob, 35), (&alice, 5)];
+        let sign_tx = SignTx::pay(&inputs, &from, &payments, 4).unwrap();
+        sign_tx.verify().unwrap();
+        assert_eq!(sign_tx.outputs.len(), 4);
+
+        let balances: Vec<u64> = sign_tx
+            .outputs
+            .iter()
+            .zip([&from, &alice, &bob, &alice])
+            .map(|(x, y)| {
+                x.decrypt_token_secrt(y.get_view_private_key())
+                    .unwrap()
+                    .balance
+            })
+            .collect();
+        assert_eq!(balances, vec![41, 20, 35, 5]);
+
+        //dropping an output is caught by the sum proof, not by an index panic, even
+        //when the inputs sign the shortened transaction
+        let mut dropped = sign_tx.clone();
+        dropped.outputs.pop();
+        dropped.sign_msgs = inputs
+            .iter()
+            .map(|x| {
+                let key = x
+                    .one_time_account
+                    .get_private_key(&from, x.blind_point, x.derivation)
+                    .unwrap();
+                signature::sign(key, &dropped.prefix_hash())
+            })
+            .collect();
+        assert_eq!(
+            dropped.verify(),
+            Err(AccountError::Token(TokenError::SumProofCountMismatch))
+        );
+
+        assert_eq!(
+            SignTx::pay(&inputs, &from, &[(&bob, 100)], 6).err(),
+            Some(AccountError::InsufficientBalance)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sign_tx_json_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let sign_tx = mint(&from, 100).transfer(&from, &to, 40, 0).unwrap();
+
+        let json = serde_json::to_string(&sign_tx).unwrap();
+        let decoded: SignTx = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.to_bytes(), sign_tx.to_bytes());
+        decoded.verify().unwrap();
+
+        let account_json = serde_json::to_string(&from).unwrap();
+        let account: Account = serde_json::from_str(&account_json).unwrap();
+        assert_eq!(account.get_spend_public_key(), from.get_spend_public_key());
     }
 }
@@ -0,0 +1,116 @@
+use crate::MAX_MEMO_SIZE;
+use ct_mlsag::error::MlsagError;
+use ct_token::error::TokenError;
+use ct_token::prove::RangeBits;
+use ct_utils::error::UtilsError;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountError {
+    Codec(UtilsError),
+    Token(TokenError),
+    Mlsag(MlsagError),
+    //the one-time account was not derived for this account's keys
+    NotOwner,
+    InvalidBase58,
+    InvalidAddressLength,
+    AddressChecksumMismatch,
+    UnknownNetwork(u8),
+    UnknownAddressKind(u8),
+    InvalidAddressKey,
+    NoInputs,
+    NoRecipients,
+    DuplicateInput,
+    InsufficientBalance,
+    MemoTooLong,
+    UnsupportedTxVersion(u8),
+    //an output amount does not fit the range proof width of the tx version
+    AmountOutOfRange(RangeBits),
+    //ring members must all spend as many outputs as the real inputs
+    RingMemberMismatch,
+    //a ring has the real inputs and at least one decoy
+    InvalidRingSize(usize),
+    NotEnoughDecoys { available: usize, required: usize },
+    //a signature or proof in the transaction does not verify
+    InvalidProof,
+    //outputs must all share one aggregate range proof or each carry their own
+    RangeProofMismatch,
+    //a sign tx has one signature and one key image per input
+    SignatureCountMismatch,
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccountError::Codec(e) => write!(f, "{}", e),
+            AccountError::Token(e) => write!(f, "{}", e),
+            AccountError::Mlsag(e) => write!(f, "{}", e),
+            AccountError::NotOwner => write!(f, "this one time account not belong to you"),
+            AccountError::InvalidBase58 => write!(f, "invalid base58 character in address"),
+            AccountError::InvalidAddressLength => write!(f, "invalid address length"),
+            AccountError::AddressChecksumMismatch => write!(f, "address checksum mismatch"),
+            AccountError::UnknownNetwork(prefix) => {
+                write!(f, "unknown address network {:#04x}", prefix)
+            }
+            AccountError::UnknownAddressKind(tag) => write!(f, "unknown address kind {}", tag),
+            AccountError::InvalidAddressKey => write!(f, "invalid address public key"),
+            AccountError::NoInputs => write!(f, "transaction has no inputs"),
+            AccountError::NoRecipients => write!(f, "transaction has no recipients"),
+            AccountError::DuplicateInput => write!(f, "input spent twice"),
+            AccountError::InsufficientBalance => write!(f, "insufficient balance"),
+            AccountError::MemoTooLong => write!(f, "memo exceeds {} bytes", MAX_MEMO_SIZE),
+            AccountError::UnsupportedTxVersion(version) => {
+                write!(f, "unsupported transaction version {}", version)
+            }
+            AccountError::AmountOutOfRange(bits) => {
+                write!(f, "amount does not fit in {} bits", bits.bits())
+            }
+            AccountError::RingMemberMismatch => write!(f, "ring members differ in size"),
+            AccountError::InvalidRingSize(size) => write!(f, "invalid ring size {}", size),
+            AccountError::NotEnoughDecoys {
+                available,
+                required,
+            } => write!(
+                f,
+                "not enough decoys: {} available, {} required",
+                available, required
+            ),
+            AccountError::InvalidProof => write!(f, "invalid transaction proof"),
+            AccountError::RangeProofMismatch => {
+                write!(f, "outputs mix aggregate and per-output range proofs")
+            }
+            AccountError::SignatureCountMismatch => {
+                write!(f, "signature count does not match the inputs")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AccountError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AccountError::Codec(e) => Some(e),
+            AccountError::Token(e) => Some(e),
+            AccountError::Mlsag(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<UtilsError> for AccountError {
+    fn from(e: UtilsError) -> AccountError {
+        AccountError::Codec(e)
+    }
+}
+
+impl From<TokenError> for AccountError {
+    fn from(e: TokenError) -> AccountError {
+        AccountError::Token(e)
+    }
+}
+
+impl From<MlsagError> for AccountError {
+    fn from(e: MlsagError) -> AccountError {
+        AccountError::Mlsag(e)
+    }
+}
@@ -1,15 +1,70 @@
+use ct_token::prove::RangeBits;
+use ct_utils::transcript::{ChainId, Label, TxContext};
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
+use error::AccountError;
 
 pub mod account;
+pub mod address;
+pub mod builder;
 pub mod confidential_transaction;
+pub mod error;
+pub mod mint_transaction;
 pub mod ring_confidential_transaction;
 
+//sha3-256 digest identifying a transaction or its unsigned prefix
+pub type TxHash = [u8; 32];
+
+//transactions carry an optional plaintext memo, bound by the signature
+pub const MAX_MEMO_SIZE: usize = 256;
+
+//version 1 proves amounts in 32 bits, version 2 in the full 64 bits of a u64
+pub const TX_VERSION: u8 = 2;
+
+//the range proof width a transaction version commits its outputs to
+pub fn range_bits(version: u8) -> Result<RangeBits, AccountError> {
+    match version {
+        1 => Ok(RangeBits::Bits32),
+        2 => Ok(RangeBits::Bits64),
+        _ => Err(AccountError::UnsupportedTxVersion(version)),
+    }
+}
+
+//the rules and the chain a transaction is built for, both are signed and every
+//proof in the transaction is bound to them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxHeader {
+    pub version: u8,
+    pub chain_id: ChainId,
+}
+
+impl Default for TxHeader {
+    fn default() -> Self {
+        TxHeader {
+            version: TX_VERSION,
+            chain_id: ChainId::default(),
+        }
+    }
+}
+
+const TX_VERSION_LABEL: Label = Label::new(b"tx_version");
+
+impl TxHeader {
+    //the proof context shared by every transaction kind, each adds what it spends
+    pub(crate) fn proof_context(&self) -> TxContext {
+        TxContext::new(self.chain_id).bind(TX_VERSION_LABEL, &[self.version])
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyPair {
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
     pub public_key: RistrettoPoint,
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
     pub private_key: Scalar,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlindPair(pub KeyPair);
 
 impl BlindPair {
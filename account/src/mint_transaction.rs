@@ -0,0 +1,273 @@
+use super::{
+    account::Account, account::Derivation, address::Address,
+    confidential_transaction::ConfidentialTransaction, error::AccountError, range_bits, TxHash,
+    TxHeader,
+};
+use ct_crypto::signature::{self, SignMsg};
+use ct_token::batch::BatchVerifier;
+use ct_token::prove::{NonnegativeProof, OpeningProof};
+use ct_token::token::Token;
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+use ct_utils::hash::Hasher;
+use ct_utils::point_to_bytes;
+use ct_utils::transcript::{ChainId, Label, TxContext};
+use curve25519_dalek::ristretto::RistrettoPoint;
+
+//creates `amount` out of nothing, the amount is public so supply can be audited.
+//The opening proof ties the output commitment to `amount` and the issuer signs the
+//whole prefix, whether the issuer may mint at all is up to the ledger.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MintTx {
+    //the protocol rules the tx was built under, see `range_bits`
+    pub version: u8,
+    //the chain the tx is valid on
+    pub chain_id: ChainId,
+    pub amount: u64,
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
+    pub issuer: RistrettoPoint,
+    pub output: ConfidentialTransaction,
+    pub opening_proof: OpeningProof,
+    pub sign_msg: SignMsg,
+}
+
+impl MintTx {
+    const PREFIX_DOMAIN: &'static [u8] = b"ct_mint_tx_prefix";
+    const TXID_DOMAIN: &'static [u8] = b"ct_mint_tx_id";
+    const ISSUER_LABEL: Label = Label::new(b"mint_issuer");
+    const AMOUNT_LABEL: Label = Label::new(b"mint_amount");
+
+    pub fn new(issuer: &Account, to: &Address, amount: u64) -> Result<MintTx, AccountError> {
+        MintTx::with_header(issuer, to, amount, TxHeader::default())
+    }
+
+    //an amount that does not fit in the version's range proof width is an error
+    pub fn with_header(
+        issuer: &Account,
+        to: &Address,
+        amount: u64,
+        header: TxHeader,
+    ) -> Result<MintTx, AccountError> {
+        let bits = range_bits(header.version)?;
+        let issuer_key = issuer.get_spend_public_key();
+        let context = Self::proof_context(header, &issuer_key, amount);
+        let (one_time_account, blind_pair, symmetric_key) = to.gen_one_time_account(0);
+        let (token, token_secrt) = Token::mint(amount);
+        let output = ConfidentialTransaction {
+            one_time_account,
+            blind_point: blind_pair.get_blind_point(),
+            derivation: Derivation::Indexed(0),
+            token,
+            nonnegative_proof: Some(NonnegativeProof::new(&token_secrt, bits, &context)?),
+            encrypto_token_secret: token_secrt
+                .encrypt(&symmetric_key, &one_time_account.get_point())?,
+        };
+        let opening_proof =
+            OpeningProof::new(&token_secrt, &point_to_bytes(&one_time_account.get_point()));
+
+        let prefix_hash =
+            Self::compute_prefix_hash(header, amount, &issuer_key, &output, &opening_proof);
+        Ok(MintTx {
+            version: header.version,
+            chain_id: header.chain_id,
+            amount,
+            issuer: issuer_key,
+            output,
+            opening_proof,
+            sign_msg: signature::sign(issuer.get_spend_private_key(), &prefix_hash),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MintTx, AccountError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
+
+    pub fn header(&self) -> TxHeader {
+        TxHeader {
+            version: self.version,
+            chain_id: self.chain_id,
+        }
+    }
+
+    //hash of everything except the issuer signature, this is the signed message
+    pub fn prefix_hash(&self) -> TxHash {
+        Self::compute_prefix_hash(
+            self.header(),
+            self.amount,
+            &self.issuer,
+            &self.output,
+            &self.opening_proof,
+        )
+    }
+
+    pub fn txid(&self) -> TxHash {
+        Hasher::domain_sha_256(Self::TXID_DOMAIN, &self.to_bytes())
+    }
+
+    fn proof_context(header: TxHeader, issuer: &RistrettoPoint, amount: u64) -> TxContext {
+        header
+            .proof_context()
+            .bind(Self::ISSUER_LABEL, &point_to_bytes(issuer))
+            .bind(Self::AMOUNT_LABEL, &amount.to_le_bytes())
+    }
+
+    fn compute_prefix_hash(
+        header: TxHeader,
+        amount: u64,
+        issuer: &RistrettoPoint,
+        output: &ConfidentialTransaction,
+        opening_proof: &OpeningProof,
+    ) -> TxHash {
+        let mut writer = Writer::new();
+        writer.write_u8(codec::WIRE_VERSION);
+        writer.write_u8(header.version);
+        header.chain_id.encode(&mut writer);
+        writer.write_u64(amount);
+        issuer.encode(&mut writer);
+        output.encode(&mut writer);
+        opening_proof.encode(&mut writer);
+        Hasher::domain_sha_256(Self::PREFIX_DOMAIN, &writer.into_bytes())
+    }
+
+    //checks the signature and proofs only, not that `issuer` is authorized
+    pub fn verify(&self) -> Result<(), AccountError> {
+        let mut batch = BatchVerifier::new();
+        self.add_to_batch(&mut batch)?;
+        batch.verify().map_err(|_| AccountError::InvalidProof)
+    }
+
+    //queues the issuer signature and the opening proof so that many transactions can
+    //be verified at once. The output must carry a range proof of its own, it cannot
+    //be batched and is checked right away.
+    pub fn add_to_batch<'a>(&'a self, batch: &mut BatchVerifier<'a>) -> Result<(), AccountError> {
+        let bits = range_bits(self.version)?;
+        let nonnegative_proof = self
+            .output
+            .nonnegative_proof
+            .as_ref()
+            .ok_or(AccountError::RangeProofMismatch)?;
+        batch.add_signature(self.issuer, &self.sign_msg, &self.prefix_hash());
+        batch.add_opening_proof(
+            &self.opening_proof,
+            &self.output.token,
+            self.amount,
+            &point_to_bytes(&self.output.one_time_account.get_point()),
+        );
+        let context = Self::proof_context(self.header(), &self.issuer, self.amount);
+        if !nonnegative_proof.verify(&self.output.token, bits, &context) {
+            return Err(AccountError::InvalidProof);
+        }
+        Ok(())
+    }
+}
+
+impl Encode for MintTx {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u8(self.version);
+        self.chain_id.encode(writer);
+        writer.write_u64(self.amount);
+        self.issuer.encode(writer);
+        self.output.encode(writer);
+        self.opening_proof.encode(writer);
+        self.sign_msg.encode(writer);
+    }
+}
+
+impl Decode for MintTx {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(MintTx {
+            version: reader.read_u8()?,
+            chain_id: ChainId::decode(reader)?,
+            amount: reader.read_u64()?,
+            issuer: RistrettoPoint::decode(reader)?,
+            output: ConfidentialTransaction::decode(reader)?,
+            opening_proof: OpeningProof::decode(reader)?,
+            sign_msg: SignMsg::decode(reader)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Network;
+    use ct_token::error::TokenError;
+    use ct_token::prove::RangeBits;
+
+    #[test]
+    fn mint_tx_test() {
+        let (issuer, to) = (Account::new(), Account::new());
+        let tx = MintTx::new(&issuer, &to.to_address(Network::Testnet), 1000).unwrap();
+        tx.verify().unwrap();
+
+        let decoded = MintTx::from_bytes(&tx.to_bytes()).unwrap();
+        assert_eq!(decoded.txid(), tx.txid());
+        decoded.verify().unwrap();
+
+        //the receiver can open the output and sees the public amount
+        let secret = tx
+            .output
+            .decrypt_token_secrt(to.get_view_private_key())
+            .unwrap();
+        assert_eq!(secret.balance, 1000);
+
+        //the commitment does not hold the claimed amount
+        let mut inflated = tx.clone();
+        inflated.amount = 1001;
+        assert_eq!(inflated.verify(), Err(AccountError::InvalidProof));
+
+        //a different key cannot take over the issuer's signature
+        let mut forged = tx;
+        forged.issuer = Account::new().get_spend_public_key();
+        assert_eq!(forged.verify(), Err(AccountError::InvalidProof));
+
+        //the amount has to fit in the width the version proves
+        let address = to.to_address(Network::Testnet);
+        let version = |version| TxHeader {
+            version,
+            ..TxHeader::default()
+        };
+        assert_eq!(
+            MintTx::with_header(&issuer, &address, 1 << 32, version(1)).err(),
+            Some(AccountError::Token(TokenError::AmountOutOfRange(
+                RangeBits::Bits32
+            )))
+        );
+        let tx = MintTx::new(&issuer, &address, u64::MAX).unwrap();
+        tx.verify().unwrap();
+        assert_eq!(
+            MintTx::with_header(&issuer, &address, 1, version(3)).err(),
+            Some(AccountError::UnsupportedTxVersion(3))
+        );
+
+        //the version is signed and decides the width the verifier expects
+        let tx = MintTx::with_header(&issuer, &address, 1000, version(1)).unwrap();
+        tx.verify().unwrap();
+        let mut edited = tx.clone();
+        edited.version = crate::TX_VERSION;
+        assert_eq!(edited.verify(), Err(AccountError::InvalidProof));
+        edited.sign_msg = signature::sign(issuer.get_spend_private_key(), &edited.prefix_hash());
+        assert_eq!(edited.verify(), Err(AccountError::InvalidProof));
+
+        //so is the chain, and the range proof is bound to it
+        let testnet = TxHeader {
+            chain_id: ChainId::TESTNET,
+            ..TxHeader::default()
+        };
+        let tx = MintTx::with_header(&issuer, &address, 1000, testnet).unwrap();
+        tx.verify().unwrap();
+        assert_eq!(
+            MintTx::from_bytes(&tx.to_bytes()).unwrap().chain_id,
+            ChainId::TESTNET
+        );
+        let mut moved = tx.clone();
+        moved.chain_id = ChainId::MAINNET;
+        moved.sign_msg = signature::sign(issuer.get_spend_private_key(), &moved.prefix_hash());
+        assert_eq!(moved.verify(), Err(AccountError::InvalidProof));
+    }
+}
@@ -1,15 +1,22 @@
 use ct_mlsag::{material::KeyPair, mlsag::Mlsag, signature::Signarute};
-use ct_token::{
-    prove::NonnegativeProof,
-    token::{EncryptoTokenSecret, Token, TokenSecret},
-};
-use ct_utils::generator::BASEPOINT_G2;
+use ct_token::batch::BatchVerifier;
+use ct_token::{error::TokenError, prove::AggregateRangeProof, token::TokenSecret};
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+use ct_utils::generator::{BASEPOINT_G1, BASEPOINT_G2};
+use ct_utils::get_random_index;
+use ct_utils::hash::Hasher;
+use ct_utils::point_to_bytes;
+use ct_utils::transcript::{ChainId, Label, TxContext};
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
 
 use crate::{
-    account::{Account, OneTimeAccount},
-    confidential_transaction::ConfidentialTransaction,
-    BlindPair,
+    account::{Account, Recipient},
+    confidential_transaction::{
+        new_outputs, verify_range_proofs, ConfidentialTransaction, NewOutputs, OutputOptions,
+    },
+    error::AccountError,
+    range_bits, TxHash, TxHeader, MAX_MEMO_SIZE,
 };
 
 type TransferAmount = u64;
@@ -19,229 +26,547 @@ pub struct RingCT {
     pub input_tx: Vec<ConfidentialTransaction>,
     pub output_account: Vec<(Account, TransferAmount)>,
     pub decoys: Vec<Vec<ConfidentialTransaction>>,
+    pub fee: u64,
 }
 
 impl RingCT {
-    pub fn transfer(&self) -> RingSignature {
-        let input_token_secrt = self.decrypt_input_token_secrt();
-        assert!(
-            input_token_secrt.iter().map(|x| x.balance).sum::<u64>()
-                == self.output_account.iter().map(|(_, x)| x).sum()
+    pub fn transfer(&self) -> Result<RingSignature, AccountError> {
+        let inputs: Vec<(&ConfidentialTransaction, &Account)> = self
+            .input_tx
+            .iter()
+            .map(|x| (x, &self.ownership_account))
+            .collect();
+        let outputs: Vec<(&dyn Recipient, u64)> = self
+            .output_account
+            .iter()
+            .map(|(x, amount)| (x as &dyn Recipient, *amount))
+            .collect();
+        RingSignature::sign(
+            &inputs,
+            &outputs,
+            self.decoys.clone(),
+            self.fee,
+            Vec::new(),
+            OutputOptions::default(),
+            TxHeader::default(),
+        )
+    }
+}
+
+impl RingSignature {
+    //spends `inputs`, each opened and signed with the keys of its own owner, hidden
+    //among `decoys`. The outputs must add up to the inputs minus the fee.
+    pub(crate) fn sign(
+        inputs: &[(&ConfidentialTransaction, &Account)],
+        outputs: &[(&dyn Recipient, u64)],
+        decoys: Vec<Vec<ConfidentialTransaction>>,
+        fee: u64,
+        memo: Vec<u8>,
+        options: OutputOptions,
+        header: TxHeader,
+    ) -> Result<RingSignature, AccountError> {
+        let bits = range_bits(header.version)?;
+        if inputs.is_empty() {
+            return Err(AccountError::NoInputs);
+        }
+        if decoys.iter().any(|x| x.len() != inputs.len()) {
+            return Err(AccountError::RingMemberMismatch);
+        }
+        if memo.len() > MAX_MEMO_SIZE {
+            return Err(AccountError::MemoTooLong);
+        }
+        let input_token_secrt = decrypt_input_token_secrt(inputs)?;
+        let input_balance = input_token_secrt
+            .iter()
+            .try_fold(0u64, |sum, x| sum.checked_add(x.balance));
+        let output_balance = outputs
+            .iter()
+            .try_fold(fee, |sum, (_, x)| sum.checked_add(*x));
+        if input_balance.is_none() || input_balance != output_balance {
+            return Err(AccountError::Token(TokenError::Unbalanced));
+        }
+
+        //the real inputs go to a random ring column, the same one they get in the
+        //MLSAG, so their position says nothing about which column is real
+        let signer_index = get_random_index(decoys.len() + 1).unwrap_or_default();
+        let mut ring = decoys;
+        ring.insert(
+            signer_index,
+            inputs.iter().map(|(x, _)| (*x).clone()).collect(),
         );
-        assert!(self.decoys.iter().all(|x| x.len() == self.input_tx.len()));
-
-        let output_one_time_account = self.generate_one_time_account();
-        let output_token = self.token_mint();
-        let encry_token_secrets = Self::encry_token_secret(&output_one_time_account, &output_token);
-        let nonnegative_proofs = Self::generate_nonnegative_proof(&output_token);
-        let output_cts = Self::get_output_cts(
-            &output_one_time_account,
-            &output_token,
-            &encry_token_secrets,
-            &nonnegative_proofs,
+        let NewOutputs {
+            outputs: output_cts,
+            secrets: output_token_secrt,
+            range_proof,
+        } = new_outputs(
+            outputs,
+            options,
+            bits,
+            &RingSignature::proof_context(header, &ring),
+        )?;
+        let sign_msg = RingSignature::compute_prefix_hash(
+            header,
+            &output_cts,
+            &range_proof,
+            &ring,
+            fee,
+            &memo,
         );
-        let sign_msg: Vec<u8> = output_cts.iter().map(|x| x.to_bytes()).flatten().collect();
 
+        let output_sum = output_cts
+            .iter()
+            .map(|x| x.token.get_point())
+            .sum::<RistrettoPoint>()
+            + Scalar::from(fee) * (*BASEPOINT_G1);
         let mut mlasg = Mlsag::default();
-        mlasg.add_signer(self.compute_signer_keypairs(&input_token_secrt, &output_token));
-        for x in &self.decoys {
-            mlasg.add_decopys(Self::compute_decoys_keypairs(&x, &output_token))
+        mlasg.add_signer(compute_signer_keypairs(
+            inputs,
+            &input_token_secrt,
+            &output_token_secrt,
+        )?);
+        for (i, x) in ring.iter().enumerate() {
+            if i != signer_index {
+                mlasg.add_decopys(compute_decoys_keypairs(x, &output_sum));
+            }
         }
-        let signarute = mlasg.sign(&sign_msg);
+        let signarute = mlasg.sign_at(&sign_msg, signer_index)?;
 
-        let mut inputs = self.decoys.clone();
-        inputs.push(self.input_tx.clone());
-        RingSignature {
+        Ok(RingSignature {
+            version: header.version,
+            chain_id: header.chain_id,
             sig: signarute,
             outputs: output_cts,
-            inputs: inputs,
-        }
+            range_proof,
+            inputs: ring,
+            fee,
+            memo,
+        })
     }
 }
 
-impl RingCT {
-    fn decrypt_input_token_secrt(&self) -> Vec<TokenSecret> {
-        self.input_tx
-            .iter()
-            .map(|x| {
-                let key = x
-                    .one_time_account
-                    .get_private_key(&self.ownership_account, x.blind_point)
-                    .unwrap();
-                x.decrypt_token_secrt(key).unwrap()
+fn decrypt_input_token_secrt(
+    inputs: &[(&ConfidentialTransaction, &Account)],
+) -> Result<Vec<TokenSecret>, AccountError> {
+    inputs
+        .iter()
+        .map(|(x, owner)| {
+            let view_account = owner.to_view_account();
+            if !x
+                .one_time_account
+                .belongs_to(&view_account, x.blind_point, x.derivation)
+            {
+                return Err(AccountError::NotOwner);
+            }
+            x.decrypt_token_secrt(view_account.get_view_private_key())
+        })
+        .collect()
+}
+
+fn compute_signer_keypairs(
+    inputs: &[(&ConfidentialTransaction, &Account)],
+    input_token_secrt: &[TokenSecret],
+    output_token_secrt: &[TokenSecret],
+) -> Result<Vec<KeyPair>, AccountError> {
+    let mut key_pairs = inputs
+        .iter()
+        .map(|(x, owner)| {
+            let private_key =
+                x.one_time_account
+                    .get_private_key(owner, x.blind_point, x.derivation)?;
+            Ok(KeyPair {
+                private_key,
+                public_key: x.one_time_account.get_point(),
             })
-            .collect()
+        })
+        .collect::<Result<Vec<KeyPair>, AccountError>>()?;
+
+    let z: Scalar = input_token_secrt.iter().map(|x| x.blind).sum::<Scalar>()
+        - output_token_secrt.iter().map(|x| x.blind).sum::<Scalar>();
+
+    key_pairs.push(KeyPair {
+        private_key: z,
+        public_key: z * (*BASEPOINT_G2),
+    });
+
+    Ok(key_pairs)
+}
+
+//the last column is sum(inputs) - sum(outputs) - fee*G1, which is a multiple of
+//G2 only for the real input row
+fn compute_decoys_keypairs(
+    decoy: &[ConfidentialTransaction],
+    output_sum: &RistrettoPoint,
+) -> Vec<KeyPair> {
+    ring_column(decoy, output_sum)
+        .into_iter()
+        .map(|public_key| KeyPair {
+            public_key,
+            private_key: Scalar::default(),
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RingSignature {
+    //the protocol rules the tx was built under, see `range_bits`
+    pub version: u8,
+    //the chain the tx is valid on
+    pub chain_id: ChainId,
+    pub sig: Signarute,
+    pub outputs: Vec<ConfidentialTransaction>,
+    //covers all outputs, see `RangeProofMode::Aggregated`
+    pub range_proof: Option<AggregateRangeProof>,
+    pub inputs: Vec<Vec<ConfidentialTransaction>>,
+    pub fee: u64,
+    //public, at most `MAX_MEMO_SIZE` bytes
+    pub memo: Vec<u8>,
+}
+
+impl RingSignature {
+    const PREFIX_DOMAIN: &'static [u8] = b"ct_ring_tx_prefix";
+    const TXID_DOMAIN: &'static [u8] = b"ct_ring_tx_id";
+    const RING_MEMBER_LABEL: Label = Label::new(b"ring_member");
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
     }
 
-    fn generate_one_time_account(&self) -> Vec<(OneTimeAccount, BlindPair, Vec<u8>)> {
-        self.output_account
-            .iter()
-            .map(|(x, _)| x.gen_one_time_account())
-            .collect()
+    pub fn from_bytes(bytes: &[u8]) -> Result<RingSignature, AccountError> {
+        Ok(codec::from_bytes(bytes)?)
     }
 
-    fn token_mint(&self) -> Vec<(Token, TokenSecret)> {
-        self.output_account
-            .iter()
-            .map(|(_, x)| Token::mint(*x))
-            .collect()
+    pub fn header(&self) -> TxHeader {
+        TxHeader {
+            version: self.version,
+            chain_id: self.chain_id,
+        }
     }
 
-    fn encry_token_secret(
-        a: &Vec<(OneTimeAccount, BlindPair, Vec<u8>)>,
-        b: &Vec<(Token, TokenSecret)>,
-    ) -> Vec<EncryptoTokenSecret> {
-        a.iter()
-            .zip(b.iter())
-            .map(|((_, _, key), (_, token_secret))| token_secret.encrypt(key).unwrap())
-            .collect()
+    //hash of the ring and outputs without the MLSAG signature, this is the signed message
+    pub fn prefix_hash(&self) -> TxHash {
+        Self::compute_prefix_hash(
+            self.header(),
+            &self.outputs,
+            &self.range_proof,
+            &self.inputs,
+            self.fee,
+            &self.memo,
+        )
     }
 
-    fn generate_nonnegative_proof(a: &Vec<(Token, TokenSecret)>) -> Vec<NonnegativeProof> {
-        a.iter().map(|(_, x)| NonnegativeProof::new(x)).collect()
+    //hash of the full canonical encoding, signature included
+    pub fn txid(&self) -> TxHash {
+        Hasher::domain_sha_256(Self::TXID_DOMAIN, &self.to_bytes())
     }
 
-    fn compute_signer_keypairs(
-        &self,
-        input_token_secrt: &Vec<TokenSecret>,
-        output_token: &Vec<(Token, TokenSecret)>,
-    ) -> Vec<KeyPair> {
-        let mut key_pairs: Vec<KeyPair> = self
-            .input_tx
-            .iter()
-            .map(|x| {
-                let private_key = x
-                    .one_time_account
-                    .get_private_key(&self.ownership_account, x.blind_point)
-                    .unwrap();
-                KeyPair {
-                    private_key: private_key,
-                    public_key: x.one_time_account.get_point(),
-                }
+    //the range proofs are bound to every one-time account in the ring, the
+    //verifier cannot tell which of them is spent
+    fn proof_context(header: TxHeader, ring: &[Vec<ConfidentialTransaction>]) -> TxContext {
+        ring.iter()
+            .flatten()
+            .fold(header.proof_context(), |context, x| {
+                context.bind(
+                    Self::RING_MEMBER_LABEL,
+                    &point_to_bytes(&x.one_time_account.get_point()),
+                )
             })
-            .collect();
+    }
 
-        let z: Scalar = input_token_secrt.iter().map(|x| x.blind).sum::<Scalar>()
-            - output_token.iter().map(|(_, x)| x.blind).sum::<Scalar>();
+    fn compute_prefix_hash(
+        header: TxHeader,
+        outputs: &[ConfidentialTransaction],
+        range_proof: &Option<AggregateRangeProof>,
+        inputs: &[Vec<ConfidentialTransaction>],
+        fee: u64,
+        memo: &[u8],
+    ) -> TxHash {
+        let mut writer = Writer::new();
+        writer.write_u8(codec::WIRE_VERSION);
+        writer.write_u8(header.version);
+        header.chain_id.encode(&mut writer);
+        outputs.encode(&mut writer);
+        range_proof.encode(&mut writer);
+        inputs.encode(&mut writer);
+        writer.write_u64(fee);
+        writer.write_bytes(memo);
+        Hasher::domain_sha_256(Self::PREFIX_DOMAIN, &writer.into_bytes())
+    }
 
-        key_pairs.push(KeyPair {
-            private_key: z,
-            public_key: z * (*BASEPOINT_G2),
-        });
+    pub fn verify(&self) -> Result<(), AccountError> {
+        let mut batch = BatchVerifier::new();
+        self.add_to_batch(&mut batch)?;
 
-        key_pairs
+        //inputs = outputs needs no separate check, the last key of every column is
+        //sum(inputs) - sum(outputs) - fee*G1 and the signer knows its discrete log.
+        //Key images are checked against spent ones by the ledger.
+        batch.verify().map_err(|_| AccountError::InvalidProof)
     }
 
-    fn compute_decoys_keypairs(
-        decoy: &Vec<ConfidentialTransaction>,
-        output_token: &Vec<(Token, TokenSecret)>,
-    ) -> Vec<KeyPair> {
-        let mut key_pairs: Vec<KeyPair> = decoy
-            .iter()
-            .map(|x| KeyPair {
-                private_key: Scalar::default(),
-                public_key: x.one_time_account.get_point(),
-            })
-            .collect();
+    //neither the MLSAG nor the range proofs can be batched, both are checked right
+    //away. It takes a batch like the other transaction kinds.
+    pub fn add_to_batch<'a>(&'a self, _batch: &mut BatchVerifier<'a>) -> Result<(), AccountError> {
+        let bits = range_bits(self.version)?;
+        if self.inputs.is_empty() {
+            return Err(AccountError::NoInputs);
+        }
+        if self.memo.len() > MAX_MEMO_SIZE {
+            return Err(AccountError::MemoTooLong);
+        }
 
-        let output_token_sum = output_token
-            .iter()
-            .map(|(x, _)| x.get_point())
-            .sum::<RistrettoPoint>();
+        //verify signature, over a ring rebuilt from the inputs it claims to spend
+        let ring = self
+            .ring_public_keys()
+            .ok_or(AccountError::RingMemberMismatch)?;
+        if !self.sig.verify(&self.prefix_hash(), &ring) {
+            return Err(AccountError::InvalidProof);
+        }
+
+        let context = RingSignature::proof_context(self.header(), &self.inputs);
+        verify_range_proofs(&self.outputs, self.range_proof.as_ref(), bits, &context)
+    }
 
-        key_pairs.push(KeyPair {
-            public_key: decoy
+    //one column per ring member: its one-time accounts, then its commitment
+    //difference. `None` if the members do not all spend the same number of inputs.
+    fn ring_public_keys(&self) -> Option<Vec<Vec<RistrettoPoint>>> {
+        let input_size = self.inputs.first()?.len();
+        if input_size == 0 || self.inputs.iter().any(|x| x.len() != input_size) {
+            return None;
+        }
+        let output_sum = self.compute_output_token_sum() + Scalar::from(self.fee) * (*BASEPOINT_G1);
+        Some(
+            self.inputs
                 .iter()
-                .map(|x| x.token.get_point())
-                .sum::<RistrettoPoint>()
-                - output_token_sum,
-            private_key: Scalar::default(),
-        });
-        key_pairs
+                .map(|x| ring_column(x, &output_sum))
+                .collect(),
+        )
+    }
+
+    fn compute_output_token_sum(&self) -> RistrettoPoint {
+        self.outputs.iter().map(|x| x.token.get_point()).sum()
     }
+}
 
-    fn get_output_cts(
-        output_one_time_account: &Vec<(OneTimeAccount, BlindPair, Vec<u8>)>,
-        output_token: &Vec<(Token, TokenSecret)>,
-        encry_token_secrets: &Vec<EncryptoTokenSecret>,
-        nonnegative_proofs: &Vec<NonnegativeProof>,
-    ) -> Vec<ConfidentialTransaction> {
-        output_one_time_account
+//the public keys of one ring member spending `members`, `output_sum` includes the fee
+fn ring_column(
+    members: &[ConfidentialTransaction],
+    output_sum: &RistrettoPoint,
+) -> Vec<RistrettoPoint> {
+    let mut column: Vec<RistrettoPoint> = members
+        .iter()
+        .map(|x| x.one_time_account.get_point())
+        .collect();
+    column.push(
+        members
             .iter()
-            .zip(output_token.iter())
-            .zip(encry_token_secrets.iter())
-            .zip(nonnegative_proofs.iter())
-            .map(
-                |((((onetime_account, blind_pair, _), (token, _)), encry), nonnegative_proof)| {
-                    ConfidentialTransaction {
-                        one_time_account: *onetime_account,
-                        blind_point: blind_pair.get_blind_point(),
-                        token: *token,
-                        nonnegative_proof: nonnegative_proof.clone(),
-                        encrypto_token_secret: encry.clone(),
-                    }
-                },
-            )
-            .collect()
+            .map(|x| x.token.get_point())
+            .sum::<RistrettoPoint>()
+            - output_sum,
+    );
+    column
+}
+
+impl Encode for RingSignature {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u8(self.version);
+        self.chain_id.encode(writer);
+        self.sig.encode(writer);
+        self.outputs.encode(writer);
+        self.range_proof.encode(writer);
+        self.inputs.encode(writer);
+        writer.write_u64(self.fee);
+        writer.write_bytes(&self.memo);
     }
 }
 
-pub struct RingSignature {
-    sig: Signarute,
-    outputs: Vec<ConfidentialTransaction>,
-    inputs: Vec<Vec<ConfidentialTransaction>>,
+impl Decode for RingSignature {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(RingSignature {
+            version: reader.read_u8()?,
+            chain_id: ChainId::decode(reader)?,
+            sig: Signarute::decode(reader)?,
+            outputs: Vec::decode(reader)?,
+            range_proof: Option::decode(reader)?,
+            inputs: Vec::decode(reader)?,
+            fee: reader.read_u64()?,
+            memo: reader.read_bytes()?,
+        })
+    }
 }
 
-impl RingSignature {
-    pub fn verify(&mut self) -> bool {
-        //verify signature
-        let sign_msg: Vec<u8> = self
-            .outputs
-            .iter()
-            .map(|x| x.to_bytes())
-            .flatten()
-            .collect();
-        if !self.sig.verify(&sign_msg) {
-            return false;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TX_VERSION;
+    use ct_utils::get_random_scalar;
 
-        println!("验证环签名, 正确!");
+    fn mint(account: &Account, amount: u64) -> ConfidentialTransaction {
+        ConfidentialTransaction::new(account, amount).unwrap()
+    }
 
-        //verify nonnegative proof
-        if !self
-            .outputs
-            .iter_mut()
-            .all(|x| x.nonnegative_proof.verify(&x.token))
-        {
-            return false;
-        }
+    fn mint_with_secret(account: &Account, amount: u64) -> (ConfidentialTransaction, TokenSecret) {
+        ConfidentialTransaction::new_with_secret(
+            account,
+            amount,
+            0,
+            OutputOptions::default(),
+            range_bits(TX_VERSION).unwrap(),
+            &TxContext::default(),
+        )
+        .unwrap()
+    }
 
-        println!("验证非负证明, 正确!");
+    #[test]
+    fn ring_signature_bytes_test() {
+        let owner = Account::new();
+        let decoy = Account::new();
+        let ring_ct = RingCT {
+            ownership_account: owner.clone(),
+            input_tx: vec![mint(&owner, 30), mint(&owner, 20)],
+            output_account: vec![(Account::new(), 50)],
+            decoys: vec![vec![mint(&decoy, 7), mint(&decoy, 9)]],
+            fee: 0,
+        };
+        let ring_sig = ring_ct.transfer().unwrap();
 
-        //verify sum proof
-        let output_token_sum = self.compute_output_token_sum();
-        if !self
-            .inputs
-            .iter()
-            .zip(self.sig.public_keys.iter())
-            .all(|(x, y)| {
-                x.iter()
-                    .map(|x| x.token.get_point())
-                    .sum::<RistrettoPoint>()
-                    == output_token_sum + y[y.len() - 1]
-            })
-        {
-            return false;
-        }
+        let bytes = ring_sig.to_bytes();
+        let decoded = RingSignature::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        decoded.verify().unwrap();
+        assert_eq!(decoded.txid(), ring_sig.txid());
+        assert_eq!(decoded.prefix_hash(), ring_sig.prefix_hash());
+        assert_ne!(ring_sig.txid(), ring_sig.prefix_hash());
+    }
+
+    #[test]
+    fn ring_signature_fee_test() {
+        let owner = Account::new();
+        let ring_ct = RingCT {
+            ownership_account: owner.clone(),
+            input_tx: vec![mint(&owner, 30)],
+            output_account: vec![(Account::new(), 25)],
+            decoys: vec![vec![mint(&Account::new(), 7)]],
+            fee: 5,
+        };
+        let ring_sig = ring_ct.transfer().unwrap();
+        ring_sig.verify().unwrap();
+
+        let mut raised = ring_sig.clone();
+        raised.fee = 6;
+        assert_eq!(raised.verify(), Err(AccountError::InvalidProof));
+    }
+
+    #[test]
+    fn ring_ct_error_test() {
+        let owner = Account::new();
+        let mut ring_ct = RingCT {
+            ownership_account: owner.clone(),
+            input_tx: vec![mint(&owner, 30)],
+            output_account: vec![(Account::new(), 31)],
+            decoys: vec![vec![mint(&Account::new(), 7)]],
+            fee: 0,
+        };
+        assert_eq!(
+            ring_ct.transfer().err(),
+            Some(AccountError::Token(TokenError::Unbalanced))
+        );
+
+        ring_ct.output_account[0].1 = 30;
+        ring_ct.decoys[0].push(mint(&Account::new(), 7));
+        assert_eq!(
+            ring_ct.transfer().err(),
+            Some(AccountError::RingMemberMismatch)
+        );
 
-        println!("验证输入= 输出, 正确!");
+        ring_ct.decoys.clear();
+        ring_ct.ownership_account = Account::new();
+        assert_eq!(ring_ct.transfer().err(), Some(AccountError::NotOwner));
+    }
+
+    #[test]
+    fn ring_signature_forged_ring_test() {
+        //whoever paid bob knows the amount and blind of his output, only his spend
+        //key is missing
+        let bob = Account::new();
+        let (input, input_secret) = mint_with_secret(&bob, 30);
+        let (output, output_secret) = mint_with_secret(&Account::new(), 30);
+        let decoy = mint(&Account::new(), 12);
 
-        //todo 验证key_images是否出现在历史交易中。
+        //so the payer signs the one-time account column with a key of their own
+        let fake_key = get_random_scalar();
+        let z = input_secret.blind - output_secret.blind;
+        let mut mlsag = Mlsag::default();
+        mlsag.add_signer(vec![
+            KeyPair {
+                private_key: fake_key,
+                public_key: fake_key * (*BASEPOINT_G2),
+            },
+            KeyPair {
+                private_key: z,
+                public_key: z * (*BASEPOINT_G2),
+            },
+        ]);
+        mlsag.add_decopys(compute_decoys_keypairs(
+            std::slice::from_ref(&decoy),
+            &output.token.get_point(),
+        ));
+        let outputs = vec![output];
+        let inputs = vec![vec![input], vec![decoy.clone()]];
+        let header = TxHeader::default();
+        let prefix_hash =
+            RingSignature::compute_prefix_hash(header, &outputs, &None, &inputs, 0, &[]);
+        let mut forged = RingSignature {
+            version: header.version,
+            chain_id: header.chain_id,
+            sig: mlsag.sign_at(&prefix_hash, 0).unwrap(),
+            outputs,
+            range_proof: None,
+            inputs,
+            fee: 0,
+            memo: Vec::new(),
+        };
+        //it closes over the keys the payer chose, the ring from the inputs differs
+        assert!(forged.sig.verify(&prefix_hash, &mlsag.ring_at(0).unwrap()));
+        assert_eq!(forged.verify(), Err(AccountError::InvalidProof));
 
-        true
+        //a ring with ragged members is rejected instead of silently truncated
+        forged.inputs[0].push(decoy);
+        assert_eq!(forged.verify(), Err(AccountError::RingMemberMismatch));
     }
 
-    fn compute_output_token_sum(&self) -> RistrettoPoint {
-        self.outputs.iter().map(|x| x.token.get_point()).sum()
+    #[test]
+    fn ring_signer_position_test() {
+        let owner = Account::new();
+        let input = mint(&owner, 10);
+        let ring_ct = RingCT {
+            ownership_account: owner,
+            input_tx: vec![input.clone()],
+            output_account: vec![(Account::new(), 10)],
+            decoys: vec![
+                vec![mint(&Account::new(), 3)],
+                vec![mint(&Account::new(), 4)],
+            ],
+            fee: 0,
+        };
+
+        //30 draws miss one of 3 positions with probability below 1/50000
+        let mut counts = [0usize; 3];
+        for i in 0..30 {
+            let ring_sig = ring_ct.transfer().unwrap();
+            let index = ring_sig
+                .inputs
+                .iter()
+                .position(|x| x[0].to_bytes() == input.to_bytes())
+                .unwrap();
+            if i < 3 {
+                ring_sig.verify().unwrap();
+            }
+            counts[index] += 1;
+        }
+        assert!(
+            counts.iter().all(|x| *x > 0),
+            "input positions {:?}",
+            counts
+        );
     }
 }
@@ -1,63 +1,62 @@
-use crypto::buffer::{BufferResult, ReadBuffer, WriteBuffer};
-use crypto::{aes, blockmodes, buffer, symmetriccipher};
+use crate::error::CryptoError;
+use crypto::aead::{AeadDecryptor, AeadEncryptor};
+use crypto::aes::KeySize;
+use crypto::aes_gcm::AesGcm;
+use rand::RngCore;
 
-const IV: [u8; 16] = [0; 16];
+//AES-256-GCM, a nonce must never be used twice under the same key
+pub const NONCE_SIZE: usize = 12;
+pub const TAG_SIZE: usize = 16;
 
-pub fn encrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
-    let mut encryptor =
-        aes::cbc_encryptor(aes::KeySize::KeySize256, key, &IV, blockmodes::PkcsPadding);
-    let mut final_result = Vec::<u8>::new();
-    let mut read_buffer = buffer::RefReadBuffer::new(data);
-    let mut buffer = [0; 4096];
-    let mut write_buffer = buffer::RefWriteBuffer::new(&mut buffer);
-
-    loop {
-        let result = encryptor.encrypt(&mut read_buffer, &mut write_buffer, true)?;
-        final_result.extend(
-            write_buffer
-                .take_read_buffer()
-                .take_remaining()
-                .iter()
-                .map(|&i| i),
-        );
-
-        match result {
-            BufferResult::BufferUnderflow => break,
-            BufferResult::BufferOverflow => {}
-        }
+//the cipher itself panics on a short key
+fn check_key(key: &[u8]) -> Result<(), CryptoError> {
+    if key.len() != 32 {
+        return Err(CryptoError::InvalidKeyLength(key.len()));
     }
+    Ok(())
+}
 
-    Ok(final_result)
+pub fn random_nonce() -> [u8; NONCE_SIZE] {
+    let mut nonce = [0u8; NONCE_SIZE];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    nonce
 }
 
+//returns the ciphertext followed by the tag, which authenticates it together
+//with `associated_data`
+pub fn encrypt(
+    data: &[u8],
+    key: &[u8],
+    nonce: &[u8; NONCE_SIZE],
+    associated_data: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    check_key(key)?;
+    let mut cipher = AesGcm::new(KeySize::KeySize256, key, nonce, associated_data);
+    let mut output = vec![0u8; data.len() + TAG_SIZE];
+    let (ciphertext, tag) = output.split_at_mut(data.len());
+    cipher.encrypt(data, ciphertext, tag);
+    Ok(output)
+}
+
+//fails with `AuthenticationFailed` unless key, nonce and associated data are the
+//ones used to encrypt and the ciphertext is untouched
 pub fn decrypt(
     encrypted_data: &[u8],
     key: &[u8],
-) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
-    let mut decryptor =
-        aes::cbc_decryptor(aes::KeySize::KeySize256, key, &IV, blockmodes::PkcsPadding);
-
-    let mut final_result = Vec::<u8>::new();
-    let mut read_buffer = buffer::RefReadBuffer::new(encrypted_data);
-    let mut buffer = [0; 4096];
-    let mut write_buffer = buffer::RefWriteBuffer::new(&mut buffer);
-
-    loop {
-        let result = decryptor.decrypt(&mut read_buffer, &mut write_buffer, true)?;
-        final_result.extend(
-            write_buffer
-                .take_read_buffer()
-                .take_remaining()
-                .iter()
-                .map(|&i| i),
-        );
-        match result {
-            BufferResult::BufferUnderflow => break,
-            BufferResult::BufferOverflow => {}
-        }
+    nonce: &[u8; NONCE_SIZE],
+    associated_data: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    check_key(key)?;
+    if encrypted_data.len() < TAG_SIZE {
+        return Err(CryptoError::Decrypt);
     }
-
-    Ok(final_result)
+    let (ciphertext, tag) = encrypted_data.split_at(encrypted_data.len() - TAG_SIZE);
+    let mut cipher = AesGcm::new(KeySize::KeySize256, key, nonce, associated_data);
+    let mut output = vec![0u8; ciphertext.len()];
+    if !cipher.decrypt(ciphertext, &mut output, tag) {
+        return Err(CryptoError::AuthenticationFailed);
+    }
+    Ok(output)
 }
 
 #[cfg(test)]
@@ -67,14 +66,40 @@ mod tests {
     fn aes_test() {
         let data = "datatoencrypt".as_bytes().to_vec();
         let key: Vec<u8> = "abcdefghijklmnopqrstuvwxyzabcdef".as_bytes().to_vec();
-        match encrypt(&data, &key) {
-            Ok(v) => match decrypt(&v, &key) {
-                Ok(expect_data) => {
-                    assert_eq!(expect_data, data)
-                }
-                Err(e) => panic!("{:?}", e),
-            },
-            Err(e) => panic!("{:?}", e),
+        let nonce = random_nonce();
+        let encrypted = encrypt(&data, &key, &nonce, b"aad").unwrap();
+        assert_eq!(encrypted.len(), data.len() + TAG_SIZE);
+        assert_eq!(decrypt(&encrypted, &key, &nonce, b"aad"), Ok(data.clone()));
+
+        assert_eq!(
+            encrypt(&data, &key[..16], &nonce, b"aad"),
+            Err(CryptoError::InvalidKeyLength(16))
+        );
+        let mut other_key = key.clone();
+        other_key[0] ^= 1;
+        assert_eq!(
+            decrypt(&encrypted, &other_key, &nonce, b"aad"),
+            Err(CryptoError::AuthenticationFailed)
+        );
+        assert_eq!(
+            decrypt(&encrypted, &key, &random_nonce(), b"aad"),
+            Err(CryptoError::AuthenticationFailed)
+        );
+        assert_eq!(
+            decrypt(&encrypted, &key, &nonce, b"other aad"),
+            Err(CryptoError::AuthenticationFailed)
+        );
+        for i in [0, encrypted.len() - 1] {
+            let mut tampered = encrypted.clone();
+            tampered[i] ^= 1;
+            assert_eq!(
+                decrypt(&tampered, &key, &nonce, b"aad"),
+                Err(CryptoError::AuthenticationFailed)
+            );
         }
+        assert_eq!(
+            decrypt(&encrypted[..TAG_SIZE - 1], &key, &nonce, b"aad"),
+            Err(CryptoError::Decrypt)
+        );
     }
 }
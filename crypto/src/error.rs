@@ -0,0 +1,39 @@
+use ct_utils::error::UtilsError;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptoError {
+    //AES-256 keys are 32 bytes
+    InvalidKeyLength(usize),
+    //the ciphertext is too short to hold its tag
+    Decrypt,
+    //wrong key, tampered ciphertext or different associated data
+    AuthenticationFailed,
+    Codec(UtilsError),
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CryptoError::InvalidKeyLength(len) => write!(f, "invalid key length {}", len),
+            CryptoError::Decrypt => write!(f, "decryption failed"),
+            CryptoError::AuthenticationFailed => write!(f, "ciphertext authentication failed"),
+            CryptoError::Codec(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CryptoError::Codec(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<UtilsError> for CryptoError {
+    fn from(e: UtilsError) -> CryptoError {
+        CryptoError::Codec(e)
+    }
+}
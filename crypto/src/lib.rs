@@ -1,2 +1,3 @@
 pub mod aes;
+pub mod error;
 pub mod signature;
@@ -1,25 +1,67 @@
-use ct_utils::{
-    generator::BASEPOINT_G2, get_random_scalar, point_to_bytes,
-    transcript::TranscriptProtocol,
-};
+use crate::error::CryptoError;
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+use ct_utils::transcript::{Label, ProofTranscript};
+use ct_utils::{generator::BASEPOINT_G2, get_random_scalar};
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar, traits::MultiscalarMul};
-use merlin::Transcript;
 
+const SIGN_LABEL: Label = Label::new(b"ct_sign");
+const PUBLIC_KEY_LABEL: Label = Label::new(b"public_key");
+const BLIND_POINT_LABEL: Label = Label::new(b"blind_point");
+const MSG_LABEL: Label = Label::new(b"msg");
+const CHALLENGE_LABEL: Label = Label::new(b"challenge");
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SignMsg {
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
     blind_point: RistrettoPoint,
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
     private_key_blind: Scalar,
 }
 
-pub fn sign(private_key: Scalar, msg: &mut Vec<u8>) -> SignMsg {
+impl SignMsg {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SignMsg, CryptoError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
+
+    //R in the verification equation R = h*public_key + s*G2
+    pub fn blind_point(&self) -> RistrettoPoint {
+        self.blind_point
+    }
+
+    //s in the verification equation R = h*public_key + s*G2
+    pub fn private_key_blind(&self) -> Scalar {
+        self.private_key_blind
+    }
+}
+
+impl Encode for SignMsg {
+    fn encode(&self, writer: &mut Writer) {
+        self.blind_point.encode(writer);
+        self.private_key_blind.encode(writer);
+    }
+}
+
+impl Decode for SignMsg {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(SignMsg {
+            blind_point: RistrettoPoint::decode(reader)?,
+            private_key_blind: Scalar::decode(reader)?,
+        })
+    }
+}
+
+pub fn sign(private_key: Scalar, msg: &[u8]) -> SignMsg {
     let public_key = RistrettoPoint::multiscalar_mul(&[private_key], &[*BASEPOINT_G2]);
     let blind = get_random_scalar();
     let blind_point = RistrettoPoint::multiscalar_mul(&[blind], &[*BASEPOINT_G2]);
 
-    let mut transcript = Transcript::new(b"ct_sign");
-    transcript.append_message(b"public_key", &point_to_bytes(&public_key));
-    transcript.append_message(b"blind_point", &point_to_bytes(&blind_point));
-    transcript.append_message(b"msg", &msg);
-    let h = transcript.challenge_scalar(b"");
+    let h = challenge(&public_key, &blind_point, msg);
     let private_key_blind = blind - h * private_key;
     SignMsg {
         blind_point,
@@ -27,33 +69,41 @@ pub fn sign(private_key: Scalar, msg: &mut Vec<u8>) -> SignMsg {
     }
 }
 
-pub fn verify(public_key: RistrettoPoint, sign: &SignMsg, msg: &mut Vec<u8>) -> bool {
-    let mut transcript = Transcript::new(b"ct_sign");
-    transcript.append_message(b"public_key", &point_to_bytes(&public_key));
-    transcript.append_message(b"blind_point", &point_to_bytes(&sign.blind_point));
-    transcript.append_message(b"msg", &msg);
-    let h = transcript.challenge_scalar(b"");
+pub fn verify(public_key: RistrettoPoint, sign: &SignMsg, msg: &[u8]) -> bool {
+    let h = challenge(&public_key, &sign.blind_point, msg);
 
     let expect_blind_point =
         RistrettoPoint::multiscalar_mul(&[h, sign.private_key_blind], &[public_key, *BASEPOINT_G2]);
     expect_blind_point == sign.blind_point
 }
 
+//h in the verification equation, for verifiers that check many signatures at once
+pub fn challenge(public_key: &RistrettoPoint, blind_point: &RistrettoPoint, msg: &[u8]) -> Scalar {
+    let mut transcript = ProofTranscript::new(SIGN_LABEL);
+    transcript.append_point(PUBLIC_KEY_LABEL, public_key);
+    transcript.append_point(BLIND_POINT_LABEL, blind_point);
+    transcript.append_message(MSG_LABEL, msg);
+    transcript.challenge_scalar(CHALLENGE_LABEL)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
     fn sign_verify_test() {
-        let msg = vec![1, 2, 3, 4, 5, 6];
+        let msg = [1, 2, 3, 4, 5, 6];
         let key = get_random_scalar();
         let key_point = RistrettoPoint::multiscalar_mul(&[key], &[*BASEPOINT_G2]);
 
-        let sign_msg = sign(key, &mut msg.to_vec());
-        let verify_result = verify(key_point, &sign_msg, &mut msg.to_vec());
-        assert_eq!(true, verify_result);
+        let sign_msg = sign(key, &msg);
+        let verify_result = verify(key_point, &sign_msg, &msg);
+        assert!(verify_result);
+
+        let decoded = SignMsg::from_bytes(&sign_msg.to_bytes()).unwrap();
+        assert_eq!(decoded, sign_msg);
 
         let key_point = RistrettoPoint::multiscalar_mul(&[get_random_scalar()], &[*BASEPOINT_G2]);
-        let verify_result = verify(key_point, &sign_msg, &mut msg.to_vec());
-        assert_eq!(false, verify_result);
+        let verify_result = verify(key_point, &sign_msg, &msg);
+        assert!(!verify_result);
     }
 }
@@ -0,0 +1,321 @@
+use crate::error::LedgerError;
+use crate::ledger::output_id;
+use crate::transaction::Transaction;
+use ct_account::TxHash;
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+use ct_utils::hash::Hasher;
+use ct_utils::merkle::merkle_root;
+use std::collections::HashSet;
+
+pub type BlockHash = [u8; 32];
+
+//the header commits to the transactions by txid and, separately, to every output
+//they create, so a light client can prove an output exists without the whole tx
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlockHeader {
+    pub height: u64,
+    pub prev_hash: BlockHash,
+    pub tx_root: [u8; 32],
+    pub output_root: [u8; 32],
+}
+
+impl BlockHeader {
+    const HASH_DOMAIN: &'static [u8] = b"ct_block_header";
+
+    pub fn hash(&self) -> BlockHash {
+        Hasher::domain_sha_256(Self::HASH_DOMAIN, &codec::to_bytes(self))
+    }
+}
+
+impl Encode for BlockHeader {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u64(self.height);
+        writer.write_raw(&self.prev_hash);
+        writer.write_raw(&self.tx_root);
+        writer.write_raw(&self.output_root);
+    }
+}
+
+impl Decode for BlockHeader {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        let height = reader.read_u64()?;
+        let mut hashes = [[0u8; 32]; 3];
+        for hash in hashes.iter_mut() {
+            *hash = reader.read_array()?;
+        }
+        Ok(BlockHeader {
+            height,
+            prev_hash: hashes[0],
+            tx_root: hashes[1],
+            output_root: hashes[2],
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub transactions: Vec<Transaction>,
+}
+
+impl Block {
+    const OUTPUT_LEAF_DOMAIN: &'static [u8] = b"ct_block_output";
+
+    pub fn new(height: u64, prev_hash: BlockHash, transactions: Vec<Transaction>) -> Block {
+        let header = BlockHeader {
+            height,
+            prev_hash,
+            tx_root: Self::compute_tx_root(&transactions),
+            output_root: Self::compute_output_root(&transactions),
+        };
+        Block {
+            header,
+            transactions,
+        }
+    }
+
+    pub fn hash(&self) -> BlockHash {
+        self.header.hash()
+    }
+
+    pub fn compute_tx_root(transactions: &[Transaction]) -> [u8; 32] {
+        let txids: Vec<TxHash> = transactions.iter().map(|x| x.txid()).collect();
+        merkle_root(&txids)
+    }
+
+    //outputs in transaction order, each leaf is the hash of the output encoding
+    pub fn compute_output_root(transactions: &[Transaction]) -> [u8; 32] {
+        let leaves: Vec<[u8; 32]> = transactions
+            .iter()
+            .flat_map(|x| x.outputs())
+            .map(|x| Hasher::domain_sha_256(Self::OUTPUT_LEAF_DOMAIN, &x.to_bytes()))
+            .collect();
+        merkle_root(&leaves)
+    }
+
+    //checks that only need the block itself: the header roots, and that no two
+    //transactions spend the same output or create the same one. Spends are compared
+    //by key image, the one identifier sign and ring spends of an output share.
+    pub fn validate(&self) -> Result<(), LedgerError> {
+        if self.header.tx_root != Self::compute_tx_root(&self.transactions)
+            || self.header.output_root != Self::compute_output_root(&self.transactions)
+        {
+            return Err(LedgerError::BlockRootMismatch);
+        }
+
+        let mut spent = HashSet::new();
+        let mut outputs = HashSet::new();
+        for tx in &self.transactions {
+            if !tx.key_images()?.into_iter().all(|x| spent.insert(x)) {
+                return Err(LedgerError::ConflictingInputInBlock);
+            }
+            if !tx
+                .outputs()
+                .iter()
+                .all(|x| outputs.insert(output_id(&x.one_time_account)))
+            {
+                return Err(LedgerError::DuplicateOutput);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Block, LedgerError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
+}
+
+impl Encode for Block {
+    fn encode(&self, writer: &mut Writer) {
+        self.header.encode(writer);
+        self.transactions.encode(writer);
+    }
+}
+
+impl Decode for Block {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(Block {
+            header: BlockHeader::decode(reader)?,
+            transactions: Vec::decode(reader)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::Ledger;
+    use ct_account::account::Account;
+    use ct_account::address::Network;
+    use ct_account::confidential_transaction::ConfidentialTransaction;
+    use ct_account::mint_transaction::MintTx;
+    use ct_account::ring_confidential_transaction::RingCT;
+
+    fn mint(account: &Account, amount: u64) -> ConfidentialTransaction {
+        ConfidentialTransaction::new(account, amount).unwrap()
+    }
+
+    #[test]
+    fn apply_block_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let input = mint(&from, 100);
+        let mut ledger = Ledger::new();
+        ledger.add_output(input.clone()).unwrap();
+
+        let tx = Transaction::Sign(Box::new(input.transfer(&from, &to, 40, 0).unwrap()));
+        let genesis = Block::new(0, [0; 32], vec![tx]);
+        assert_eq!(
+            Block::from_bytes(&genesis.to_bytes()).unwrap().hash(),
+            genesis.hash()
+        );
+
+        //a tampered root no longer matches the transactions
+        let mut tampered = genesis.clone();
+        tampered.header.output_root = [0; 32];
+        assert_eq!(
+            ledger.apply_block(&tampered),
+            Err(LedgerError::BlockRootMismatch)
+        );
+
+        ledger.apply_block(&genesis).unwrap();
+        assert_eq!(ledger.tip(), Some(&genesis.header));
+        assert_eq!(ledger.get_block(0).unwrap().hash(), genesis.hash());
+        assert!(ledger.get_output(&input.one_time_account).is_none());
+        assert_eq!(ledger.outputs().count(), 2);
+
+        //the next block has to link to the tip
+        let block = Block::new(1, [0; 32], vec![]);
+        assert_eq!(
+            ledger.apply_block(&block),
+            Err(LedgerError::BlockNotExtendingTip)
+        );
+        let block = Block::new(2, genesis.hash(), vec![]);
+        assert_eq!(
+            ledger.apply_block(&block),
+            Err(LedgerError::BlockNotExtendingTip)
+        );
+        let block = Block::new(1, genesis.hash(), vec![]);
+        ledger.apply_block(&block).unwrap();
+        assert_eq!(ledger.tip().unwrap().height, 1);
+    }
+
+    #[test]
+    fn block_batch_verify_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let inputs = vec![mint(&from, 100), mint(&from, 50)];
+        let mut ledger = Ledger::new();
+        for input in &inputs {
+            ledger.add_output(input.clone()).unwrap();
+        }
+
+        //each transaction passes the state checks, one signature is forged
+        let valid = inputs[0].transfer(&from, &to, 40, 0).unwrap();
+        let mut forged = inputs[1].transfer(&from, &to, 20, 0).unwrap();
+        forged.sign_msgs = inputs[1].transfer(&from, &to, 30, 0).unwrap().sign_msgs;
+        let block = Block::new(
+            0,
+            [0; 32],
+            vec![
+                Transaction::Sign(Box::new(valid.clone())),
+                Transaction::Sign(Box::new(forged)),
+            ],
+        );
+        assert_eq!(ledger.apply_block(&block), Err(LedgerError::InvalidProof));
+        assert!(ledger.tip().is_none());
+
+        let spend = inputs[1].transfer(&from, &to, 20, 0).unwrap();
+        let block = Block::new(
+            0,
+            [0; 32],
+            vec![
+                Transaction::Sign(Box::new(valid)),
+                Transaction::Sign(Box::new(spend)),
+            ],
+        );
+        ledger.apply_block(&block).unwrap();
+        assert_eq!(ledger.outputs().count(), 4);
+    }
+
+    #[test]
+    fn block_supply_test() {
+        let (issuer, to) = (Account::new(), Account::new());
+        let address = to.to_address(Network::Testnet);
+        let mut ledger = Ledger::new();
+        ledger.authorize_issuer(issuer.get_spend_public_key());
+        let mint =
+            |amount| Transaction::Mint(Box::new(MintTx::new(&issuer, &address, amount).unwrap()));
+
+        //each mint fits on its own, the two together do not
+        let half = u64::MAX / 2 + 1;
+        let block = Block::new(0, [0; 32], vec![mint(half), mint(half)]);
+        assert!(ledger.validate_transaction(&block.transactions[0]).is_ok());
+        assert_eq!(ledger.apply_block(&block), Err(LedgerError::SupplyOverflow));
+        assert_eq!(ledger.supply(), 0);
+
+        let block = Block::new(0, [0; 32], vec![mint(half), mint(half - 1)]);
+        ledger.apply_block(&block).unwrap();
+        assert_eq!(ledger.supply(), u64::MAX);
+    }
+
+    #[test]
+    fn block_conflict_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let input = mint(&from, 100);
+        let decoy = mint(&Account::new(), 7);
+        let mut ledger = Ledger::new();
+        ledger.add_output(input.clone()).unwrap();
+        ledger.add_output(decoy.clone()).unwrap();
+
+        //each spend is valid alone, together they spend the input twice
+        let txs = vec![
+            Transaction::Sign(Box::new(input.transfer(&from, &to, 40, 0).unwrap())),
+            Transaction::Sign(Box::new(input.transfer(&from, &to, 10, 0).unwrap())),
+        ];
+        let block = Block::new(0, [0; 32], txs);
+        assert_eq!(
+            ledger.apply_block(&block),
+            Err(LedgerError::ConflictingInputInBlock)
+        );
+
+        let ring_ct = RingCT {
+            ownership_account: from.clone(),
+            input_tx: vec![input.clone()],
+            output_account: vec![(Account::new(), 100)],
+            decoys: vec![vec![decoy]],
+            fee: 0,
+        };
+        let txs = vec![
+            Transaction::Ring(Box::new(ring_ct.transfer().unwrap())),
+            Transaction::Ring(Box::new(ring_ct.transfer().unwrap())),
+        ];
+        let block = Block::new(0, [0; 32], txs);
+        assert_eq!(
+            ledger.apply_block(&block),
+            Err(LedgerError::ConflictingInputInBlock)
+        );
+
+        //a sign spend and a ring spend of the same input, in either order
+        let sign = Transaction::Sign(Box::new(input.transfer(&from, &to, 40, 0).unwrap()));
+        let ring = Transaction::Ring(Box::new(ring_ct.transfer().unwrap()));
+        for txs in [
+            vec![sign.clone(), ring.clone()],
+            vec![ring.clone(), sign.clone()],
+        ] {
+            let block = Block::new(0, [0; 32], txs);
+            assert_eq!(block.validate(), Err(LedgerError::ConflictingInputInBlock));
+            assert_eq!(
+                ledger.apply_block(&block),
+                Err(LedgerError::ConflictingInputInBlock)
+            );
+        }
+
+        //nothing from the rejected blocks was committed
+        assert!(ledger.tip().is_none());
+        assert_eq!(ledger.outputs().count(), 2);
+    }
+}
@@ -0,0 +1,85 @@
+use ct_account::error::AccountError;
+use ct_utils::error::UtilsError;
+use std::fmt;
+use std::io::ErrorKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerError {
+    Codec(UtilsError),
+    Account(AccountError),
+    //the tx was built for another chain
+    WrongChain,
+    UnknownInput,
+    //the tx carries a copy of the input that differs from the ledger's
+    InputMismatch,
+    KeyImageSpent,
+    DuplicateOutput,
+    InvalidProof,
+    UnauthorizedIssuer,
+    SupplyOverflow,
+    //ring rows of different sizes or a key image count that does not match them
+    MalformedRing,
+    BlockRootMismatch,
+    //two transactions in the block burn the same key image, i.e. spend one output
+    ConflictingInputInBlock,
+    BlockNotExtendingTip,
+    AlreadyInPool,
+    ConflictsWithPool,
+    MempoolFull,
+    StorageIo(ErrorKind),
+    CorruptedSnapshot,
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LedgerError::Codec(e) => write!(f, "{}", e),
+            LedgerError::Account(e) => write!(f, "{}", e),
+            LedgerError::WrongChain => write!(f, "transaction is for another chain"),
+            LedgerError::UnknownInput => write!(f, "unknown or spent input"),
+            LedgerError::InputMismatch => write!(f, "input does not match ledger output"),
+            LedgerError::KeyImageSpent => write!(f, "key image already spent"),
+            LedgerError::DuplicateOutput => write!(f, "duplicate output one-time account"),
+            LedgerError::InvalidProof => write!(f, "invalid transaction proof"),
+            LedgerError::UnauthorizedIssuer => write!(f, "unauthorized issuer"),
+            LedgerError::SupplyOverflow => write!(f, "supply overflow"),
+            LedgerError::MalformedRing => write!(f, "malformed ring"),
+            LedgerError::BlockRootMismatch => write!(f, "block root mismatch"),
+            LedgerError::ConflictingInputInBlock => write!(f, "conflicting input in block"),
+            LedgerError::BlockNotExtendingTip => write!(f, "block does not extend the tip"),
+            LedgerError::AlreadyInPool => write!(f, "transaction already in pool"),
+            LedgerError::ConflictsWithPool => write!(f, "conflicts with pool transaction"),
+            LedgerError::MempoolFull => write!(f, "mempool is full"),
+            LedgerError::StorageIo(kind) => write!(f, "storage io error: {:?}", kind),
+            LedgerError::CorruptedSnapshot => write!(f, "corrupted snapshot"),
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LedgerError::Codec(e) => Some(e),
+            LedgerError::Account(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<UtilsError> for LedgerError {
+    fn from(e: UtilsError) -> LedgerError {
+        LedgerError::Codec(e)
+    }
+}
+
+impl From<AccountError> for LedgerError {
+    fn from(e: AccountError) -> LedgerError {
+        LedgerError::Account(e)
+    }
+}
+
+impl From<std::io::Error> for LedgerError {
+    fn from(e: std::io::Error) -> LedgerError {
+        LedgerError::StorageIo(e.kind())
+    }
+}
@@ -0,0 +1,562 @@
+use crate::block::{Block, BlockHeader};
+use crate::error::LedgerError;
+use crate::storage::{MemoryStorage, Record, Storage};
+use crate::transaction::Transaction;
+use ct_account::account::OneTimeAccount;
+use ct_account::builder::{sample_decoys, DecoySource};
+use ct_account::confidential_transaction::{ConfidentialTransaction, SignTx};
+use ct_account::error::AccountError;
+use ct_account::mint_transaction::MintTx;
+use ct_account::ring_confidential_transaction::RingSignature;
+use ct_account::TxHash;
+use ct_token::batch::BatchVerifier;
+use ct_utils::transcript::ChainId;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use std::collections::HashSet;
+
+//outputs are indexed by the compressed bytes of their one-time account
+pub type OutputId = [u8; 32];
+
+//compressed MLSAG key image
+pub type KeyImage = [u8; 32];
+
+pub fn output_id(one_time_account: &OneTimeAccount) -> OutputId {
+    one_time_account.get_point().compress().to_bytes()
+}
+
+//unspent outputs plus the key images already consumed. Every spend burns the key
+//image of its real input. `SignTx` spends also remove their input; `RingSignature`
+//spends cannot reveal it, so ring members stay in the set.
+//All of it lives in `storage`, which is read through on every lookup.
+//Issuers and the chain id are configuration rather than state, they are not persisted.
+#[derive(Default)]
+pub struct Ledger<S: Storage = MemoryStorage> {
+    storage: S,
+    issuers: HashSet<[u8; 32]>,
+    chain_id: ChainId,
+}
+
+impl Ledger {
+    pub fn new() -> Ledger {
+        Ledger::default()
+    }
+}
+
+impl<S: Storage> Ledger<S> {
+    //continue from whatever the storage has committed
+    pub fn open(storage: S) -> Result<Ledger<S>, LedgerError> {
+        Ok(Ledger {
+            storage,
+            issuers: HashSet::new(),
+            chain_id: ChainId::default(),
+        })
+    }
+
+    //insert an output that does not come from a transaction, e.g. genesis allocations
+    pub fn add_output(&mut self, output: ConfidentialTransaction) -> Result<(), LedgerError> {
+        self.check_new_outputs(std::slice::from_ref(&output))?;
+        self.commit(vec![Record::AddOutput(Box::new(output))])
+    }
+
+    pub fn get_output(
+        &self,
+        one_time_account: &OneTimeAccount,
+    ) -> Option<&ConfidentialTransaction> {
+        self.storage.get_output(&output_id(one_time_account))
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &ConfidentialTransaction> {
+        self.storage.outputs()
+    }
+
+    pub fn get_transaction(&self, txid: &TxHash) -> Option<&Transaction> {
+        self.storage.get_transaction(txid)
+    }
+
+    pub fn is_key_image_spent(&self, key_image: &RistrettoPoint) -> bool {
+        self.storage
+            .contains_key_image(&key_image.compress().to_bytes())
+    }
+
+    //allow `public_key` to sign mint transactions
+    pub fn authorize_issuer(&mut self, public_key: RistrettoPoint) {
+        self.issuers.insert(public_key.compress().to_bytes());
+    }
+
+    pub fn is_authorized_issuer(&self, public_key: &RistrettoPoint) -> bool {
+        self.issuers.contains(&public_key.compress().to_bytes())
+    }
+
+    //defaults to `ChainId::MAINNET`, transactions built for any other chain are rejected
+    pub fn set_chain_id(&mut self, chain_id: ChainId) {
+        self.chain_id = chain_id;
+    }
+
+    pub fn chain_id(&self) -> ChainId {
+        self.chain_id
+    }
+
+    pub fn supply(&self) -> u64 {
+        self.storage.supply()
+    }
+
+    //header of the last applied block
+    pub fn tip(&self) -> Option<&BlockHeader> {
+        self.storage.get_tip()
+    }
+
+    pub fn get_block(&self, height: u64) -> Option<Block> {
+        let (header, txids) = self.storage.get_block(height)?;
+        let transactions = txids
+            .iter()
+            .map(|x| self.get_transaction(x).cloned())
+            .collect::<Option<_>>()?;
+        Some(Block {
+            header: header.clone(),
+            transactions,
+        })
+    }
+
+    pub fn validate_sign_tx(&self, tx: &SignTx) -> Result<(), LedgerError> {
+        let mut batch = BatchVerifier::new();
+        self.check_sign_tx(tx, &mut batch)?;
+        Self::verify_batch(&batch)
+    }
+
+    //all checks run before anything is committed, so a rejected tx leaves the ledger unchanged
+    pub fn apply_sign_tx(&mut self, tx: &SignTx) -> Result<(), LedgerError> {
+        self.apply_transaction(&Transaction::Sign(Box::new(tx.clone())))
+    }
+
+    pub fn validate_ring_signature(&self, tx: &RingSignature) -> Result<(), LedgerError> {
+        let mut batch = BatchVerifier::new();
+        self.check_ring_signature(tx, &mut batch)?;
+        Self::verify_batch(&batch)
+    }
+
+    pub fn apply_ring_signature(&mut self, tx: &RingSignature) -> Result<(), LedgerError> {
+        self.apply_transaction(&Transaction::Ring(Box::new(tx.clone())))
+    }
+
+    pub fn validate_mint_tx(&self, tx: &MintTx) -> Result<(), LedgerError> {
+        let mut batch = BatchVerifier::new();
+        self.check_mint_tx(tx, &mut batch)?;
+        Self::verify_batch(&batch)
+    }
+
+    pub fn apply_mint_tx(&mut self, tx: &MintTx) -> Result<(), LedgerError> {
+        self.apply_transaction(&Transaction::Mint(Box::new(tx.clone())))
+    }
+
+    //the checks against the current state only, without the proofs. A transaction
+    //that was fully validated once only needs these again after the state changed.
+    pub fn check_state(&self, tx: &Transaction) -> Result<(), LedgerError> {
+        match tx {
+            Transaction::Sign(tx) => self.check_sign_tx_state(tx),
+            Transaction::Ring(tx) => self.check_ring_signature_state(tx),
+            Transaction::Mint(tx) => self.check_mint_tx_state(tx),
+        }
+    }
+
+    pub fn validate_transaction(&self, tx: &Transaction) -> Result<(), LedgerError> {
+        let mut batch = BatchVerifier::new();
+        self.check_transaction(tx, &mut batch)?;
+        Self::verify_batch(&batch)
+    }
+
+    pub fn apply_transaction(&mut self, tx: &Transaction) -> Result<(), LedgerError> {
+        self.validate_transaction(tx)?;
+        self.commit(Self::transaction_records(tx)?)
+    }
+
+    //every transaction is checked against the state before the block, so an output
+    //created in a block can only be spent from the next one. The proofs of all its
+    //transactions are verified together and the whole block is committed as one batch.
+    pub fn validate_block(&self, block: &Block) -> Result<(), LedgerError> {
+        let (height, prev_hash) = match self.tip() {
+            Some(tip) => (tip.height + 1, tip.hash()),
+            None => (0, [0; 32]),
+        };
+        if block.header.height != height || block.header.prev_hash != prev_hash {
+            return Err(LedgerError::BlockNotExtendingTip);
+        }
+        block.validate()?;
+        //each mint is checked against the supply before the block, the total of all
+        //of them has to fit as well
+        block
+            .transactions
+            .iter()
+            .try_fold(self.supply(), |sum, x| sum.checked_add(x.minted()))
+            .ok_or(LedgerError::SupplyOverflow)?;
+        let mut batch = BatchVerifier::new();
+        for tx in &block.transactions {
+            self.check_transaction(tx, &mut batch)?;
+        }
+        Self::verify_batch(&batch)
+    }
+
+    pub fn apply_block(&mut self, block: &Block) -> Result<(), LedgerError> {
+        self.validate_block(block)?;
+        let mut batch = Vec::new();
+        for tx in &block.transactions {
+            batch.extend(Self::transaction_records(tx)?);
+        }
+        let txids = block.transactions.iter().map(|x| x.txid()).collect();
+        batch.push(Record::AddBlock(block.header.clone(), txids));
+        self.commit(batch)
+    }
+}
+
+impl<S: Storage> Ledger<S> {
+    //checks `tx` against the state and queues its proofs in `batch`
+    fn check_transaction<'a>(
+        &self,
+        tx: &'a Transaction,
+        batch: &mut BatchVerifier<'a>,
+    ) -> Result<(), LedgerError> {
+        match tx {
+            Transaction::Sign(tx) => self.check_sign_tx(tx, batch),
+            Transaction::Ring(tx) => self.check_ring_signature(tx, batch),
+            Transaction::Mint(tx) => self.check_mint_tx(tx, batch),
+        }
+    }
+
+    fn check_sign_tx<'a>(
+        &self,
+        tx: &'a SignTx,
+        batch: &mut BatchVerifier<'a>,
+    ) -> Result<(), LedgerError> {
+        self.check_sign_tx_state(tx)?;
+        Ok(tx.add_to_batch(batch)?)
+    }
+
+    fn check_sign_tx_state(&self, tx: &SignTx) -> Result<(), LedgerError> {
+        self.check_chain(tx.chain_id)?;
+        for input in &tx.inputs {
+            self.check_known_input(input)?;
+        }
+        self.check_key_images(&Transaction::sign_key_images(tx))?;
+        self.check_new_outputs(&tx.outputs)
+    }
+
+    fn check_ring_signature<'a>(
+        &self,
+        tx: &'a RingSignature,
+        batch: &mut BatchVerifier<'a>,
+    ) -> Result<(), LedgerError> {
+        self.check_ring_signature_state(tx)?;
+        Ok(tx.add_to_batch(batch)?)
+    }
+
+    fn check_ring_signature_state(&self, tx: &RingSignature) -> Result<(), LedgerError> {
+        self.check_chain(tx.chain_id)?;
+        for input in tx.inputs.iter().flatten() {
+            self.check_known_input(input)?;
+        }
+        self.check_key_images(&Transaction::ring_key_images(tx)?)?;
+        self.check_new_outputs(&tx.outputs)
+    }
+
+    fn check_mint_tx<'a>(
+        &self,
+        tx: &'a MintTx,
+        batch: &mut BatchVerifier<'a>,
+    ) -> Result<(), LedgerError> {
+        self.check_mint_tx_state(tx)?;
+        Ok(tx.add_to_batch(batch)?)
+    }
+
+    fn check_mint_tx_state(&self, tx: &MintTx) -> Result<(), LedgerError> {
+        self.check_chain(tx.chain_id)?;
+        if !self.is_authorized_issuer(&tx.issuer) {
+            return Err(LedgerError::UnauthorizedIssuer);
+        }
+        if self.supply().checked_add(tx.amount).is_none() {
+            return Err(LedgerError::SupplyOverflow);
+        }
+        self.check_new_outputs(std::slice::from_ref(&tx.output))
+    }
+
+    fn verify_batch(batch: &BatchVerifier) -> Result<(), LedgerError> {
+        batch.verify().map_err(|_| LedgerError::InvalidProof)
+    }
+
+    fn commit(&mut self, batch: Vec<Record>) -> Result<(), LedgerError> {
+        self.storage.commit(&batch)
+    }
+
+    fn transaction_records(tx: &Transaction) -> Result<Vec<Record>, LedgerError> {
+        let mut batch: Vec<Record> = tx
+            .spent_outputs()
+            .into_iter()
+            .map(Record::SpendOutput)
+            .collect();
+        batch.extend(tx.key_images()?.into_iter().map(Record::SpendKeyImage));
+        batch.extend(
+            tx.outputs()
+                .iter()
+                .map(|x| Record::AddOutput(Box::new(x.clone()))),
+        );
+        batch.push(Record::AddTransaction(tx.clone()));
+        Ok(batch)
+    }
+
+    //the proofs only verify for the chain the tx names, this makes sure it is ours
+    fn check_chain(&self, chain_id: ChainId) -> Result<(), LedgerError> {
+        if chain_id != self.chain_id {
+            return Err(LedgerError::WrongChain);
+        }
+        Ok(())
+    }
+
+    //the tx carries a full copy of each input, it must match what the ledger holds
+    fn check_known_input(&self, input: &ConfidentialTransaction) -> Result<(), LedgerError> {
+        match self.get_output(&input.one_time_account) {
+            Some(output) if output.to_bytes() == input.to_bytes() => Ok(()),
+            Some(_) => Err(LedgerError::InputMismatch),
+            None => Err(LedgerError::UnknownInput),
+        }
+    }
+
+    fn check_key_images(&self, key_images: &[KeyImage]) -> Result<(), LedgerError> {
+        let mut seen = HashSet::with_capacity(key_images.len());
+        for key_image in key_images {
+            if self.storage.contains_key_image(key_image) || !seen.insert(key_image) {
+                return Err(LedgerError::KeyImageSpent);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_new_outputs(&self, outputs: &[ConfidentialTransaction]) -> Result<(), LedgerError> {
+        let mut seen = HashSet::with_capacity(outputs.len());
+        for output in outputs {
+            let id = output_id(&output.one_time_account);
+            if self.storage.get_output(&id).is_some() || !seen.insert(id) {
+                return Err(LedgerError::DuplicateOutput);
+            }
+        }
+        Ok(())
+    }
+}
+
+//ring members are drawn from the whole unspent set
+impl<S: Storage> DecoySource for Ledger<S> {
+    fn decoys(
+        &self,
+        count: usize,
+        size: usize,
+        exclude: &[OneTimeAccount],
+    ) -> Result<Vec<Vec<ConfidentialTransaction>>, AccountError> {
+        sample_decoys(self.outputs(), count, size, exclude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ct_account::account::Account;
+    use ct_account::address::Network;
+    use ct_account::builder::TransactionBuilder;
+    use ct_account::ring_confidential_transaction::RingCT;
+
+    fn mint(account: &Account, amount: u64) -> ConfidentialTransaction {
+        ConfidentialTransaction::new(account, amount).unwrap()
+    }
+
+    #[test]
+    fn sign_tx_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let input = mint(&from, 100);
+        let mut ledger = Ledger::new();
+        ledger.add_output(input.clone()).unwrap();
+        assert!(ledger.add_output(input.clone()).is_err());
+
+        let tx = input.transfer(&from, &to, 40, 0).unwrap();
+        ledger.apply_sign_tx(&tx).unwrap();
+        assert!(ledger.get_output(&input.one_time_account).is_none());
+        assert!(ledger.get_output(&tx.outputs[1].one_time_account).is_some());
+        assert_eq!(ledger.outputs().count(), 2);
+
+        //double spend of the same input
+        let tx = input.transfer(&from, &to, 10, 0).unwrap();
+        assert_eq!(ledger.apply_sign_tx(&tx), Err(LedgerError::UnknownInput));
+
+        //input that never reached the ledger
+        let tx = mint(&from, 5).transfer(&from, &to, 5, 0).unwrap();
+        assert_eq!(ledger.apply_sign_tx(&tx), Err(LedgerError::UnknownInput));
+        assert_eq!(ledger.outputs().count(), 2);
+    }
+
+    #[test]
+    fn mint_tx_test() {
+        let (issuer, to) = (Account::new(), Account::new());
+        let address = to.to_address(Network::Testnet);
+        let tx = MintTx::new(&issuer, &address, 500).unwrap();
+
+        let mut ledger = Ledger::new();
+        assert_eq!(
+            ledger.apply_mint_tx(&tx),
+            Err(LedgerError::UnauthorizedIssuer)
+        );
+        ledger.authorize_issuer(issuer.get_spend_public_key());
+        ledger.apply_mint_tx(&tx).unwrap();
+        assert_eq!(ledger.supply(), 500);
+        assert!(ledger.get_output(&tx.output.one_time_account).is_some());
+
+        //a replayed mint recreates an existing output
+        assert_eq!(ledger.apply_mint_tx(&tx), Err(LedgerError::DuplicateOutput));
+
+        //the minted output is spendable like any other
+        let spend = tx.output.transfer(&to, &Account::new(), 200, 0).unwrap();
+        ledger.apply_sign_tx(&spend).unwrap();
+        assert_eq!(ledger.supply(), 500);
+
+        let mut inflated = MintTx::new(&issuer, &address, 10).unwrap();
+        inflated.amount = 10_000;
+        assert_eq!(
+            ledger.apply_mint_tx(&inflated),
+            Err(LedgerError::Account(AccountError::InvalidProof))
+        );
+        assert_eq!(ledger.supply(), 500);
+    }
+
+    #[test]
+    fn chain_id_test() {
+        let (from, to) = (Account::new(), Account::new());
+        let input = mint(&from, 100);
+        let mut ledger = Ledger::new();
+        ledger.set_chain_id(ChainId::TESTNET);
+        ledger.add_output(input.clone()).unwrap();
+
+        let mainnet_tx = input.transfer(&from, &to, 40, 0).unwrap();
+        assert_eq!(
+            ledger.apply_sign_tx(&mainnet_tx),
+            Err(LedgerError::WrongChain)
+        );
+        let issuer = Account::new();
+        ledger.authorize_issuer(issuer.get_spend_public_key());
+        let mint_tx = MintTx::new(&issuer, &to.to_address(Network::Testnet), 5).unwrap();
+        assert_eq!(ledger.apply_mint_tx(&mint_tx), Err(LedgerError::WrongChain));
+        assert_eq!(ledger.outputs().count(), 1);
+
+        let testnet_tx = TransactionBuilder::new()
+            .input(&input, &from)
+            .recipient(&to, 40)
+            .chain_id(ChainId::TESTNET)
+            .build_sign_tx()
+            .unwrap();
+        ledger.apply_sign_tx(&testnet_tx).unwrap();
+        assert_eq!(ledger.chain_id(), ChainId::TESTNET);
+    }
+
+    #[test]
+    fn ring_signature_test() {
+        let (owner, decoy) = (Account::new(), Account::new());
+        let inputs = vec![mint(&owner, 30)];
+        let decoys = vec![vec![mint(&decoy, 8)]];
+        let mut ledger = Ledger::new();
+        for x in inputs.iter().chain(decoys.iter().flatten()) {
+            ledger.add_output(x.clone()).unwrap();
+        }
+
+        let ring_ct = RingCT {
+            ownership_account: owner.clone(),
+            input_tx: inputs.clone(),
+            output_account: vec![(Account::new(), 30)],
+            decoys: decoys.clone(),
+            fee: 0,
+        };
+        let tx = ring_ct.transfer().unwrap();
+        ledger.apply_ring_signature(&tx).unwrap();
+        assert!(ledger.is_key_image_spent(&tx.sig.key_images[0]));
+        assert_eq!(ledger.outputs().count(), 3);
+
+        //a second spend of the same input links through its key image
+        let tx = RingCT {
+            output_account: vec![(Account::new(), 30)],
+            ..ring_ct
+        }
+        .transfer()
+        .unwrap();
+        assert_eq!(
+            ledger.apply_ring_signature(&tx),
+            Err(LedgerError::KeyImageSpent)
+        );
+
+        //decoys must be ledger outputs too
+        let tx = RingCT {
+            ownership_account: owner.clone(),
+            input_tx: vec![mint(&owner, 4)],
+            output_account: vec![(Account::new(), 4)],
+            decoys,
+            fee: 0,
+        }
+        .transfer()
+        .unwrap();
+        assert_eq!(
+            ledger.apply_ring_signature(&tx),
+            Err(LedgerError::UnknownInput)
+        );
+        assert_eq!(ledger.outputs().count(), 3);
+    }
+
+    #[test]
+    fn ring_then_sign_spend_test() {
+        let (owner, to) = (Account::new(), Account::new());
+        let input = mint(&owner, 30);
+        let mut ledger = Ledger::new();
+        ledger.add_output(input.clone()).unwrap();
+        ledger.add_output(mint(&Account::new(), 8)).unwrap();
+
+        let ring_sig = TransactionBuilder::new()
+            .input(&input, &owner)
+            .recipient(&to, 30)
+            .decoys(&ledger, 2)
+            .build_ring_signature()
+            .unwrap();
+        ledger.apply_ring_signature(&ring_sig).unwrap();
+
+        //the ring spend left the input in the set, its key image still links it
+        assert!(ledger.get_output(&input.one_time_account).is_some());
+        let sign_tx = input.transfer(&owner, &to, 30, 0).unwrap();
+        assert_eq!(sign_tx.key_images()[0], ring_sig.sig.key_images[0]);
+        assert_eq!(
+            ledger.apply_sign_tx(&sign_tx),
+            Err(LedgerError::KeyImageSpent)
+        );
+    }
+
+    #[test]
+    fn ledger_decoy_source_test() {
+        let (owner, to) = (Account::new(), Account::new());
+        let input = mint(&owner, 50);
+        let mut ledger = Ledger::new();
+        ledger.add_output(input.clone()).unwrap();
+        for amount in 1..=4 {
+            ledger.add_output(mint(&Account::new(), amount)).unwrap();
+        }
+
+        let tx = TransactionBuilder::new()
+            .input(&input, &owner)
+            .recipient(&to, 45)
+            .fee(2)
+            .decoys(&ledger, 5)
+            .build_ring_signature()
+            .unwrap();
+        assert_eq!(tx.inputs.len(), 5);
+        ledger.apply_ring_signature(&tx).unwrap();
+
+        let builder = TransactionBuilder::new()
+            .input(&tx.outputs[1], &to)
+            .recipient(&owner, 40)
+            .decoys(&ledger, 8);
+        assert_eq!(
+            builder.build_ring_signature().err(),
+            Some(AccountError::NotEnoughDecoys {
+                available: 6,
+                required: 7,
+            })
+        );
+    }
+}
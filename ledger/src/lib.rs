@@ -0,0 +1,6 @@
+pub mod block;
+pub mod error;
+pub mod ledger;
+pub mod mempool;
+pub mod storage;
+pub mod transaction;
@@ -0,0 +1,420 @@
+use crate::block::Block;
+use crate::error::LedgerError;
+use crate::ledger::{output_id, KeyImage, Ledger, OutputId};
+use crate::storage::Storage;
+use crate::transaction::Transaction;
+use ct_account::TxHash;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+//what happens when a new transaction spends an input or key image that a pooled
+//transaction already spends
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReplacementPolicy {
+    //keep the pooled transaction and reject the newcomer
+    FirstSeen,
+    //drop every conflicting pooled transaction in favour of the newcomer
+    ReplaceConflicts,
+    //replace only if the newcomer pays more than all of its conflicts together
+    HigherFee,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct MempoolConfig {
+    //once full, the lowest-fee transaction, the oldest among equals, is evicted to
+    //make room. A newcomer paying less than every pooled transaction is rejected.
+    pub max_transactions: usize,
+    pub replacement: ReplacementPolicy,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> MempoolConfig {
+        MempoolConfig {
+            max_transactions: 10_000,
+            replacement: ReplacementPolicy::FirstSeen,
+        }
+    }
+}
+
+//verified transactions waiting for a block. No two pooled transactions spend the
+//same input, burn the same key image or create the same one-time account, so any
+//subset of the pool can go into a block together.
+#[derive(Default)]
+pub struct Mempool {
+    config: MempoolConfig,
+    transactions: HashMap<TxHash, (u64, Transaction)>,
+    arrival: BTreeMap<u64, TxHash>,
+    //(fee, arrival sequence), the first entry is the next to be evicted
+    by_fee: BTreeMap<(u64, u64), TxHash>,
+    spent_outputs: HashMap<OutputId, TxHash>,
+    key_images: HashMap<KeyImage, TxHash>,
+    outputs: HashMap<OutputId, TxHash>,
+    sequence: u64,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Mempool {
+        Mempool {
+            config,
+            ..Mempool::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    pub fn contains(&self, txid: &TxHash) -> bool {
+        self.transactions.contains_key(txid)
+    }
+
+    pub fn get(&self, txid: &TxHash) -> Option<&Transaction> {
+        self.transactions.get(txid).map(|(_, tx)| tx)
+    }
+
+    //verifies `tx` against the ledger and adds it, returning the txids of the
+    //pooled transactions it replaced or evicted
+    pub fn insert<S: Storage>(
+        &mut self,
+        ledger: &Ledger<S>,
+        tx: Transaction,
+    ) -> Result<Vec<TxHash>, LedgerError> {
+        let txid = tx.txid();
+        if self.contains(&txid) {
+            return Err(LedgerError::AlreadyInPool);
+        }
+        if self.config.max_transactions == 0 {
+            return Err(LedgerError::MempoolFull);
+        }
+        let conflicts = self.conflicts(&tx)?;
+        if !conflicts.is_empty() && !self.can_replace(&tx, &conflicts) {
+            return Err(LedgerError::ConflictsWithPool);
+        }
+        if self.len() - conflicts.len() >= self.config.max_transactions {
+            let lowest = self
+                .by_fee
+                .iter()
+                .find(|(_, x)| !conflicts.contains(*x))
+                .map(|((fee, _), _)| *fee);
+            if lowest.is_some_and(|fee| tx.fee() < fee) {
+                return Err(LedgerError::MempoolFull);
+            }
+        }
+        ledger.validate_transaction(&tx)?;
+
+        let mut removed: Vec<TxHash> = conflicts.into_iter().collect();
+        for x in &removed {
+            self.remove(x);
+        }
+        while self.len() >= self.config.max_transactions {
+            let (_, lowest) = self.by_fee.pop_first().ok_or(LedgerError::MempoolFull)?;
+            self.remove(&lowest);
+            removed.push(lowest);
+        }
+
+        for id in tx.spent_outputs() {
+            self.spent_outputs.insert(id, txid);
+        }
+        for key_image in tx.key_images()? {
+            self.key_images.insert(key_image, txid);
+        }
+        for output in tx.outputs() {
+            self.outputs
+                .insert(output_id(&output.one_time_account), txid);
+        }
+        self.arrival.insert(self.sequence, txid);
+        self.by_fee.insert((tx.fee(), self.sequence), txid);
+        self.transactions.insert(txid, (self.sequence, tx));
+        self.sequence += 1;
+        Ok(removed)
+    }
+
+    pub fn remove(&mut self, txid: &TxHash) -> Option<Transaction> {
+        let (sequence, tx) = self.transactions.remove(txid)?;
+        self.arrival.remove(&sequence);
+        self.by_fee.remove(&(tx.fee(), sequence));
+        for id in tx.spent_outputs() {
+            self.spent_outputs.remove(&id);
+        }
+        for key_image in tx.key_images().unwrap_or_default() {
+            self.key_images.remove(&key_image);
+        }
+        for output in tx.outputs() {
+            self.outputs.remove(&output_id(&output.one_time_account));
+        }
+        Some(tx)
+    }
+
+    //up to `limit` transactions in arrival order, safe to put in one block
+    pub fn candidates(&self, limit: usize) -> Vec<Transaction> {
+        self.arrival
+            .values()
+            .take(limit)
+            .map(|x| self.transactions[x].1.clone())
+            .collect()
+    }
+
+    //call once `block` is applied to `ledger`: drops what the block included and
+    //every transaction the new state invalidated, e.g. one whose ring members or
+    //key images the block spent. Returns the txids dropped without being included.
+    pub fn remove_block<S: Storage>(&mut self, ledger: &Ledger<S>, block: &Block) -> Vec<TxHash> {
+        for tx in &block.transactions {
+            self.remove(&tx.txid());
+        }
+        let invalid: Vec<TxHash> = self
+            .transactions
+            .iter()
+            .filter(|(_, (_, tx))| ledger.check_state(tx).is_err())
+            .map(|(txid, _)| *txid)
+            .collect();
+        for x in &invalid {
+            self.remove(x);
+        }
+        invalid
+    }
+
+    fn can_replace(&self, tx: &Transaction, conflicts: &HashSet<TxHash>) -> bool {
+        match self.config.replacement {
+            ReplacementPolicy::FirstSeen => false,
+            ReplacementPolicy::ReplaceConflicts => true,
+            ReplacementPolicy::HigherFee => {
+                let replaced = conflicts
+                    .iter()
+                    .filter_map(|x| self.get(x))
+                    .fold(0u64, |sum, x| sum.saturating_add(x.fee()));
+                tx.fee() > replaced
+            }
+        }
+    }
+
+    fn conflicts(&self, tx: &Transaction) -> Result<HashSet<TxHash>, LedgerError> {
+        let mut conflicts = HashSet::new();
+        for id in tx.spent_outputs() {
+            conflicts.extend(self.spent_outputs.get(&id));
+        }
+        for key_image in tx.key_images()? {
+            conflicts.extend(self.key_images.get(&key_image));
+        }
+        for output in tx.outputs() {
+            conflicts.extend(self.outputs.get(&output_id(&output.one_time_account)));
+        }
+        Ok(conflicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ct_account::account::Account;
+    use ct_account::confidential_transaction::ConfidentialTransaction;
+    use ct_account::ring_confidential_transaction::RingCT;
+
+    fn mint(account: &Account, amount: u64) -> ConfidentialTransaction {
+        ConfidentialTransaction::new(account, amount).unwrap()
+    }
+
+    fn sign_tx(input: &ConfidentialTransaction, from: &Account, amount: u64) -> Transaction {
+        sign_tx_with_fee(input, from, amount, 0)
+    }
+
+    fn sign_tx_with_fee(
+        input: &ConfidentialTransaction,
+        from: &Account,
+        amount: u64,
+        fee: u64,
+    ) -> Transaction {
+        Transaction::Sign(Box::new(
+            input.transfer(from, &Account::new(), amount, fee).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn mempool_conflict_test() {
+        let from = Account::new();
+        let (input, decoy) = (mint(&from, 100), mint(&Account::new(), 7));
+        let mut ledger = Ledger::new();
+        ledger.add_output(input.clone()).unwrap();
+        ledger.add_output(decoy.clone()).unwrap();
+
+        let mut pool = Mempool::default();
+        let first = sign_tx(&input, &from, 40);
+        assert!(pool.insert(&ledger, first.clone()).unwrap().is_empty());
+        assert_eq!(
+            pool.insert(&ledger, first.clone()),
+            Err(LedgerError::AlreadyInPool)
+        );
+        assert_eq!(
+            pool.insert(&ledger, sign_tx(&input, &from, 10)),
+            Err(LedgerError::ConflictsWithPool)
+        );
+
+        //a ring spend of the same input does not reveal it, but it burns the same
+        //key image as the sign spend
+        let ring_ct = RingCT {
+            ownership_account: from.clone(),
+            input_tx: vec![input.clone()],
+            output_account: vec![(Account::new(), 100)],
+            decoys: vec![vec![decoy]],
+            fee: 0,
+        };
+        let ring = Transaction::Ring(Box::new(ring_ct.transfer().unwrap()));
+        assert_eq!(
+            pool.insert(&ledger, ring.clone()),
+            Err(LedgerError::ConflictsWithPool)
+        );
+
+        //unverifiable transactions never enter the pool
+        let unknown = sign_tx(&mint(&from, 5), &from, 5);
+        assert_eq!(
+            pool.insert(&ledger, unknown),
+            Err(LedgerError::UnknownInput)
+        );
+        assert_eq!(pool.len(), 1);
+
+        //and the other way round, a second ring spend links through its key image too
+        let mut pool = Mempool::default();
+        pool.insert(&ledger, ring).unwrap();
+        assert_eq!(
+            pool.insert(
+                &ledger,
+                Transaction::Ring(Box::new(ring_ct.transfer().unwrap()))
+            ),
+            Err(LedgerError::ConflictsWithPool)
+        );
+        assert_eq!(
+            pool.insert(&ledger, first.clone()),
+            Err(LedgerError::ConflictsWithPool)
+        );
+
+        //with replacement the newcomer evicts the conflicting transaction
+        let mut pool = Mempool::new(MempoolConfig {
+            replacement: ReplacementPolicy::ReplaceConflicts,
+            ..MempoolConfig::default()
+        });
+        pool.insert(&ledger, first.clone()).unwrap();
+        let second = sign_tx(&input, &from, 10);
+        assert_eq!(pool.insert(&ledger, second.clone()), Ok(vec![first.txid()]));
+        assert!(!pool.contains(&first.txid()));
+        assert!(pool.contains(&second.txid()));
+
+        //fee based replacement needs a strictly higher fee
+        let mut pool = Mempool::new(MempoolConfig {
+            replacement: ReplacementPolicy::HigherFee,
+            ..MempoolConfig::default()
+        });
+        let low = sign_tx_with_fee(&input, &from, 10, 3);
+        pool.insert(&ledger, low.clone()).unwrap();
+        assert_eq!(
+            pool.insert(&ledger, sign_tx_with_fee(&input, &from, 10, 3)),
+            Err(LedgerError::ConflictsWithPool)
+        );
+        let high = sign_tx_with_fee(&input, &from, 10, 4);
+        assert_eq!(pool.insert(&ledger, high), Ok(vec![low.txid()]));
+    }
+
+    #[test]
+    fn mempool_block_test() {
+        let from = Account::new();
+        let inputs: Vec<ConfidentialTransaction> = (0..3).map(|_| mint(&from, 50)).collect();
+        let mut ledger = Ledger::new();
+        for x in &inputs {
+            ledger.add_output(x.clone()).unwrap();
+        }
+
+        //the oldest transaction is evicted once the pool is full
+        let mut pool = Mempool::new(MempoolConfig {
+            max_transactions: 2,
+            ..MempoolConfig::default()
+        });
+        let txs: Vec<Transaction> = inputs.iter().map(|x| sign_tx(x, &from, 20)).collect();
+        for tx in &txs[..2] {
+            pool.insert(&ledger, tx.clone()).unwrap();
+        }
+        assert_eq!(
+            pool.insert(&ledger, txs[2].clone()),
+            Ok(vec![txs[0].txid()])
+        );
+
+        let candidates = pool.candidates(10);
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].txid(), txs[1].txid());
+        let block = Block::new(0, [0; 32], candidates);
+        ledger.apply_block(&block).unwrap();
+
+        //the evicted transaction can come back, the included ones are gone
+        pool.insert(&ledger, txs[0].clone()).unwrap();
+        assert!(pool.remove_block(&ledger, &block).is_empty());
+        assert_eq!(pool.len(), 1);
+        assert!(pool.contains(&txs[0].txid()));
+    }
+
+    #[test]
+    fn mempool_revalidate_test() {
+        let (owner, decoy_owner) = (Account::new(), Account::new());
+        let (input, decoy) = (mint(&owner, 30), mint(&decoy_owner, 8));
+        let mut ledger = Ledger::new();
+        ledger.add_output(input.clone()).unwrap();
+        ledger.add_output(decoy.clone()).unwrap();
+
+        let ring = Transaction::Ring(Box::new(
+            RingCT {
+                ownership_account: owner,
+                input_tx: vec![input],
+                output_account: vec![(Account::new(), 30)],
+                decoys: vec![vec![decoy.clone()]],
+                fee: 0,
+            }
+            .transfer()
+            .unwrap(),
+        ));
+        let mut pool = Mempool::default();
+        pool.insert(&ledger, ring.clone()).unwrap();
+
+        //the block spends the decoy, so the pooled ring no longer verifies against
+        //the ledger even though nothing in the block conflicts with it directly
+        let block = Block::new(0, [0; 32], vec![sign_tx(&decoy, &decoy_owner, 8)]);
+        ledger.apply_block(&block).unwrap();
+        assert_eq!(pool.remove_block(&ledger, &block), vec![ring.txid()]);
+        assert!(pool.is_empty());
+        assert!(pool.candidates(10).is_empty());
+    }
+
+    #[test]
+    fn mempool_eviction_test() {
+        let from = Account::new();
+        let inputs: Vec<ConfidentialTransaction> = (0..4).map(|_| mint(&from, 50)).collect();
+        let mut ledger = Ledger::new();
+        for x in &inputs {
+            ledger.add_output(x.clone()).unwrap();
+        }
+        let txs: Vec<Transaction> = inputs
+            .iter()
+            .zip([3, 1, 2, 0])
+            .map(|(x, fee)| sign_tx_with_fee(x, &from, 20, fee))
+            .collect();
+
+        let mut pool = Mempool::new(MempoolConfig {
+            max_transactions: 2,
+            ..MempoolConfig::default()
+        });
+        pool.insert(&ledger, txs[0].clone()).unwrap();
+        pool.insert(&ledger, txs[1].clone()).unwrap();
+
+        //the lowest fee goes first, not the oldest
+        assert_eq!(
+            pool.insert(&ledger, txs[2].clone()),
+            Ok(vec![txs[1].txid()])
+        );
+        assert!(pool.contains(&txs[0].txid()));
+
+        //a newcomer paying less than everything pooled is turned away
+        assert_eq!(
+            pool.insert(&ledger, txs[3].clone()),
+            Err(LedgerError::MempoolFull)
+        );
+        assert_eq!(pool.len(), 2);
+    }
+}
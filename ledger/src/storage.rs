@@ -0,0 +1,622 @@
+use crate::block::BlockHeader;
+use crate::error::LedgerError;
+use crate::ledger::{output_id, KeyImage, OutputId};
+use crate::transaction::Transaction;
+use ct_account::confidential_transaction::ConfidentialTransaction;
+use ct_account::TxHash;
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+use ct_utils::hash::Hasher;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+//a single ledger state change, one applied transaction is committed as one batch
+#[derive(Clone)]
+pub enum Record {
+    AddOutput(Box<ConfidentialTransaction>),
+    SpendOutput(OutputId),
+    SpendKeyImage(KeyImage),
+    AddTransaction(Transaction),
+    //header and txids of an applied block, the transactions have their own records
+    AddBlock(BlockHeader, Vec<TxHash>),
+}
+
+impl Record {
+    const ADD_OUTPUT_TAG: u8 = 0;
+    const SPEND_OUTPUT_TAG: u8 = 1;
+    const SPEND_KEY_IMAGE_TAG: u8 = 2;
+    const ADD_TRANSACTION_TAG: u8 = 3;
+    const ADD_BLOCK_TAG: u8 = 4;
+}
+
+impl Encode for Record {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            Record::AddOutput(output) => {
+                writer.write_u8(Self::ADD_OUTPUT_TAG);
+                output.encode(writer);
+            }
+            Record::SpendOutput(id) => {
+                writer.write_u8(Self::SPEND_OUTPUT_TAG);
+                writer.write_raw(id);
+            }
+            Record::SpendKeyImage(key_image) => {
+                writer.write_u8(Self::SPEND_KEY_IMAGE_TAG);
+                writer.write_raw(key_image);
+            }
+            Record::AddTransaction(tx) => {
+                writer.write_u8(Self::ADD_TRANSACTION_TAG);
+                tx.encode(writer);
+            }
+            Record::AddBlock(header, txids) => {
+                writer.write_u8(Self::ADD_BLOCK_TAG);
+                header.encode(writer);
+                txids.encode(writer);
+            }
+        }
+    }
+}
+
+impl Decode for Record {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        match reader.read_u8()? {
+            Self::ADD_OUTPUT_TAG => Ok(Record::AddOutput(Box::new(
+                ConfidentialTransaction::decode(reader)?,
+            ))),
+            Self::SPEND_OUTPUT_TAG => Ok(Record::SpendOutput(reader.read_array()?)),
+            Self::SPEND_KEY_IMAGE_TAG => Ok(Record::SpendKeyImage(reader.read_array()?)),
+            Self::ADD_TRANSACTION_TAG => Ok(Record::AddTransaction(Transaction::decode(reader)?)),
+            Self::ADD_BLOCK_TAG => Ok(Record::AddBlock(
+                BlockHeader::decode(reader)?,
+                Vec::decode(reader)?,
+            )),
+            tag => Err(UtilsError::UnknownTag {
+                kind: "storage record",
+                tag,
+            }),
+        }
+    }
+}
+
+//the ledger holds no state of its own, it reads everything back through here
+pub trait Storage {
+    //durably persist one batch, after a crash a batch is recovered entirely or not at all
+    fn commit(&mut self, batch: &[Record]) -> Result<(), LedgerError>;
+
+    fn get_output(&self, id: &OutputId) -> Option<&ConfidentialTransaction>;
+
+    fn outputs(&self) -> Box<dyn Iterator<Item = &ConfidentialTransaction> + '_>;
+
+    fn contains_key_image(&self, key_image: &KeyImage) -> bool;
+
+    fn get_transaction(&self, txid: &TxHash) -> Option<&Transaction>;
+
+    fn get_tip(&self) -> Option<&BlockHeader>;
+
+    //blocks are applied in height order starting at 0
+    fn get_block(&self, height: u64) -> Option<&(BlockHeader, Vec<TxHash>)>;
+
+    //total amount created by mint transactions
+    fn supply(&self) -> u64;
+}
+
+//keeps the compacted state only: spent outputs are dropped rather than logged
+#[derive(Default, Clone)]
+pub struct MemoryStorage {
+    outputs: HashMap<OutputId, ConfidentialTransaction>,
+    key_images: HashSet<KeyImage>,
+    transactions: HashMap<TxHash, Transaction>,
+    blocks: Vec<(BlockHeader, Vec<TxHash>)>,
+    supply: u64,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    //the ledger rejects mints past `u64::MAX` before committing, a batch that still
+    //overflows the supply is refused whole rather than clamped
+    fn check(&self, batch: &[Record]) -> Result<(), LedgerError> {
+        batch
+            .iter()
+            .filter_map(|x| match x {
+                Record::AddTransaction(tx) => Some(tx.minted()),
+                _ => None,
+            })
+            .try_fold(self.supply, |sum, x| sum.checked_add(x))
+            .ok_or(LedgerError::SupplyOverflow)?;
+        Ok(())
+    }
+
+    //every committed change, compacted, in an order that can be replayed
+    fn records(&self) -> Vec<Record> {
+        let transactions = self
+            .transactions
+            .values()
+            .cloned()
+            .map(Record::AddTransaction);
+        let outputs = self
+            .outputs
+            .values()
+            .map(|x| Record::AddOutput(Box::new(x.clone())));
+        let key_images = self.key_images.iter().copied().map(Record::SpendKeyImage);
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|(header, txids)| Record::AddBlock(header.clone(), txids.clone()));
+        transactions
+            .chain(outputs)
+            .chain(key_images)
+            .chain(blocks)
+            .collect()
+    }
+
+    fn apply(&mut self, record: &Record) {
+        match record {
+            Record::AddOutput(output) => {
+                self.outputs
+                    .insert(output_id(&output.one_time_account), *output.clone());
+            }
+            Record::SpendOutput(id) => {
+                self.outputs.remove(id);
+            }
+            Record::SpendKeyImage(key_image) => {
+                self.key_images.insert(*key_image);
+            }
+            Record::AddTransaction(tx) => {
+                self.supply += tx.minted();
+                self.transactions.insert(tx.txid(), tx.clone());
+            }
+            Record::AddBlock(header, txids) => {
+                self.blocks.push((header.clone(), txids.clone()));
+            }
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn commit(&mut self, batch: &[Record]) -> Result<(), LedgerError> {
+        self.check(batch)?;
+        batch.iter().for_each(|x| self.apply(x));
+        Ok(())
+    }
+
+    fn get_output(&self, id: &OutputId) -> Option<&ConfidentialTransaction> {
+        self.outputs.get(id)
+    }
+
+    fn outputs(&self) -> Box<dyn Iterator<Item = &ConfidentialTransaction> + '_> {
+        Box::new(self.outputs.values())
+    }
+
+    fn contains_key_image(&self, key_image: &KeyImage) -> bool {
+        self.key_images.contains(key_image)
+    }
+
+    fn get_transaction(&self, txid: &TxHash) -> Option<&Transaction> {
+        self.transactions.get(txid)
+    }
+
+    fn get_tip(&self) -> Option<&BlockHeader> {
+        self.blocks.last().map(|(header, _)| header)
+    }
+
+    //the height is the index
+    fn get_block(&self, height: u64) -> Option<&(BlockHeader, Vec<TxHash>)> {
+        self.blocks.get(usize::try_from(height).ok()?)
+    }
+
+    fn supply(&self) -> u64 {
+        self.supply
+    }
+}
+
+//append-only log of checksummed batches in `dir/ledger.log`, compacted into
+//`dir/ledger.snapshot` every `snapshot_interval` commits. Each log entry and
+//the snapshot carry a sequence number, so entries already covered by the
+//snapshot are skipped and a torn entry at the tail is cut off on open.
+pub struct FileStorage {
+    dir: PathBuf,
+    log: File,
+    //end of the last complete entry
+    log_len: u64,
+    //a failed append may have left bytes past `log_len` that could not be cut yet
+    torn: bool,
+    sequence: u64,
+    snapshot_interval: u64,
+    since_snapshot: u64,
+    state: MemoryStorage,
+}
+
+impl FileStorage {
+    const LOG_FILE: &'static str = "ledger.log";
+    const SNAPSHOT_FILE: &'static str = "ledger.snapshot";
+    const SNAPSHOT_TMP_FILE: &'static str = "ledger.snapshot.tmp";
+    const CHECKSUM_DOMAIN: &'static [u8] = b"ct_ledger_storage";
+    const CHECKSUM_SIZE: usize = 8;
+
+    pub fn open<P: AsRef<Path>>(
+        dir: P,
+        snapshot_interval: u64,
+    ) -> Result<FileStorage, LedgerError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut state = MemoryStorage::new();
+        let mut sequence = 0;
+        if let Some(body) = Self::read_file(&dir.join(Self::SNAPSHOT_FILE))? {
+            let body = Self::check_entry(&body).ok_or(LedgerError::CorruptedSnapshot)?;
+            let mut reader = Reader::new(body);
+            sequence = reader.read_u64()?;
+            state.commit(&Vec::<Record>::decode(&mut reader)?)?;
+            reader.finish()?;
+        }
+
+        let log_path = dir.join(Self::LOG_FILE);
+        let log_bytes = Self::read_file(&log_path)?.unwrap_or_default();
+        let mut offset = 0;
+        let mut since_snapshot = 0;
+        while let Some((body, size)) = Self::next_entry(&log_bytes[offset..]) {
+            let mut reader = Reader::new(body);
+            let entry_sequence = reader.read_u64()?;
+            let batch = Vec::<Record>::decode(&mut reader)?;
+            if entry_sequence > sequence {
+                state.commit(&batch)?;
+                sequence = entry_sequence;
+                since_snapshot += 1;
+            }
+            offset += size;
+        }
+
+        let mut log = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&log_path)?;
+        //drop a partially written tail so new entries start on a clean boundary
+        log.set_len(offset as u64)
+            .and_then(|_| log.seek(SeekFrom::End(0)))
+            .and_then(|_| log.sync_all())?;
+
+        Ok(FileStorage {
+            dir,
+            log,
+            log_len: offset as u64,
+            torn: false,
+            sequence,
+            snapshot_interval,
+            since_snapshot,
+            state,
+        })
+    }
+
+    //write the compacted state next to the log, then empty the log
+    pub fn snapshot(&mut self) -> Result<(), LedgerError> {
+        let mut writer = Writer::new();
+        writer.write_u64(self.sequence);
+        self.state.records().encode(&mut writer);
+        let entry = Self::seal_entry(writer.into_bytes());
+
+        let tmp_path = self.dir.join(Self::SNAPSHOT_TMP_FILE);
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&entry)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp_path, self.dir.join(Self::SNAPSHOT_FILE)))?;
+        Self::sync_dir(&self.dir)?;
+
+        self.log_len = 0;
+        self.cut_log()?;
+        self.since_snapshot = 0;
+        Ok(())
+    }
+
+    //drop everything after the last complete entry and append from there
+    fn cut_log(&mut self) -> std::io::Result<()> {
+        self.log
+            .set_len(self.log_len)
+            .and_then(|_| self.log.seek(SeekFrom::Start(self.log_len)))
+            .and_then(|_| self.log.sync_all())?;
+        self.torn = false;
+        Ok(())
+    }
+
+    fn read_file(path: &Path) -> Result<Option<Vec<u8>>, LedgerError> {
+        let mut file = match File::open(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    #[cfg(unix)]
+    fn sync_dir(dir: &Path) -> Result<(), LedgerError> {
+        File::open(dir)
+            .and_then(|x| x.sync_all())
+            .map_err(LedgerError::from)
+    }
+
+    #[cfg(not(unix))]
+    fn sync_dir(_dir: &Path) -> Result<(), LedgerError> {
+        Ok(())
+    }
+
+    fn checksum(body: &[u8]) -> Vec<u8> {
+        Hasher::domain_sha_256(Self::CHECKSUM_DOMAIN, body)[..Self::CHECKSUM_SIZE].to_vec()
+    }
+
+    //entry layout: body length | body | checksum(body)
+    fn seal_entry(body: Vec<u8>) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.write_u8(codec::WIRE_VERSION);
+        writer.write_bytes(&body);
+        writer.write_raw(&Self::checksum(&body));
+        writer.into_bytes()
+    }
+
+    fn check_entry(entry: &[u8]) -> Option<&[u8]> {
+        match Self::next_entry(entry) {
+            Some((body, size)) if size == entry.len() => Some(body),
+            _ => None,
+        }
+    }
+
+    //the first complete, intact entry and its size on disk
+    fn next_entry(data: &[u8]) -> Option<(&[u8], usize)> {
+        let mut reader = Reader::new(data);
+        if reader.read_u8().ok()? != codec::WIRE_VERSION {
+            return None;
+        }
+        let len = reader.read_len().ok()?;
+        let body = reader.read_raw(len).ok()?;
+        let checksum = reader.read_raw(Self::CHECKSUM_SIZE).ok()?;
+        if Self::checksum(body) != checksum {
+            return None;
+        }
+        Some((body, data.len() - reader.remaining()))
+    }
+}
+
+impl Storage for FileStorage {
+    fn commit(&mut self, batch: &[Record]) -> Result<(), LedgerError> {
+        //a logged batch is replayed on every open, one the state refuses would make
+        //the store impossible to open again
+        self.state.check(batch)?;
+        let mut writer = Writer::new();
+        writer.write_u64(self.sequence + 1);
+        batch.encode(&mut writer);
+        let entry = Self::seal_entry(writer.into_bytes());
+
+        //open stops at the first bad entry, so nothing may be appended after torn
+        //bytes or it would be lost on the next open
+        if self.torn {
+            self.cut_log()?;
+        }
+        if let Err(e) = self
+            .log
+            .write_all(&entry)
+            .and_then(|_| self.log.sync_data())
+        {
+            self.torn = true;
+            let _ = self.cut_log();
+            return Err(e.into());
+        }
+        self.log_len += entry.len() as u64;
+        self.sequence += 1;
+        batch.iter().for_each(|x| self.state.apply(x));
+
+        self.since_snapshot += 1;
+        if self.snapshot_interval > 0 && self.since_snapshot >= self.snapshot_interval {
+            self.snapshot()?;
+        }
+        Ok(())
+    }
+
+    fn get_output(&self, id: &OutputId) -> Option<&ConfidentialTransaction> {
+        self.state.get_output(id)
+    }
+
+    fn outputs(&self) -> Box<dyn Iterator<Item = &ConfidentialTransaction> + '_> {
+        self.state.outputs()
+    }
+
+    fn contains_key_image(&self, key_image: &KeyImage) -> bool {
+        self.state.contains_key_image(key_image)
+    }
+
+    fn get_transaction(&self, txid: &TxHash) -> Option<&Transaction> {
+        self.state.get_transaction(txid)
+    }
+
+    fn get_tip(&self) -> Option<&BlockHeader> {
+        self.state.get_tip()
+    }
+
+    fn get_block(&self, height: u64) -> Option<&(BlockHeader, Vec<TxHash>)> {
+        self.state.get_block(height)
+    }
+
+    fn supply(&self) -> u64 {
+        self.state.supply()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::ledger::Ledger;
+    use ct_account::account::Account;
+    use ct_account::address::Network;
+    use ct_account::mint_transaction::MintTx;
+
+    fn mint(account: &Account, amount: u64) -> ConfidentialTransaction {
+        ConfidentialTransaction::new(account, amount).unwrap()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ct_ledger_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn output_ids<S: Storage>(ledger: &Ledger<S>) -> HashSet<OutputId> {
+        ledger
+            .outputs()
+            .map(|x| output_id(&x.one_time_account))
+            .collect()
+    }
+
+    #[test]
+    fn file_storage_recovery_test() {
+        let dir = temp_dir("recovery");
+        let (from, to) = (Account::new(), Account::new());
+        let input = mint(&from, 100);
+
+        let mut ledger = Ledger::open(FileStorage::open(&dir, 0).unwrap()).unwrap();
+        ledger.add_output(input.clone()).unwrap();
+        let tx = input.transfer(&from, &to, 40, 0).unwrap();
+        let block = Block::new(0, [0; 32], vec![Transaction::Sign(Box::new(tx.clone()))]);
+        ledger.apply_block(&block).unwrap();
+        let expected = output_ids(&ledger);
+        drop(ledger);
+
+        //simulate a crash in the middle of appending the next entry
+        let mut log = OpenOptions::new()
+            .append(true)
+            .open(dir.join(FileStorage::LOG_FILE))
+            .unwrap();
+        log.write_all(&[codec::WIRE_VERSION, 200, 0, 0, 0, 1, 2])
+            .unwrap();
+        drop(log);
+
+        let mut ledger = Ledger::open(FileStorage::open(&dir, 0).unwrap()).unwrap();
+        assert_eq!(output_ids(&ledger), expected);
+        assert!(ledger.get_transaction(&tx.txid()).is_some());
+        assert_eq!(ledger.get_block(0).unwrap().hash(), block.hash());
+        assert_eq!(ledger.apply_sign_tx(&tx), Err(LedgerError::UnknownInput));
+
+        //the torn tail was cut, so later entries are readable again
+        ledger.add_output(mint(&to, 1)).unwrap();
+        let expected = output_ids(&ledger);
+        drop(ledger);
+        let ledger = Ledger::open(FileStorage::open(&dir, 0).unwrap()).unwrap();
+        assert_eq!(output_ids(&ledger), expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn memory_storage_supply_test() {
+        let (issuer, to) = (Account::new(), Account::new());
+        let address = to.to_address(Network::Testnet);
+        let mint = |amount| {
+            Record::AddTransaction(Transaction::Mint(Box::new(
+                MintTx::new(&issuer, &address, amount).unwrap(),
+            )))
+        };
+
+        let mut storage = MemoryStorage::new();
+        storage.commit(&[mint(u64::MAX - 1)]).unwrap();
+        //an overflowing batch is refused whole instead of clamping the supply
+        assert_eq!(
+            storage.commit(&[mint(1), mint(1)]),
+            Err(LedgerError::SupplyOverflow)
+        );
+        assert_eq!(storage.supply(), u64::MAX - 1);
+        assert_eq!(storage.transactions.len(), 1);
+    }
+
+    #[test]
+    fn file_storage_failed_write_test() {
+        let dir = temp_dir("failed_write");
+        let account = Account::new();
+        let outputs: Vec<ConfidentialTransaction> = (1..=3).map(|x| mint(&account, x)).collect();
+        let add = |x: &ConfidentialTransaction| vec![Record::AddOutput(Box::new(x.clone()))];
+        let log_path = dir.join(FileStorage::LOG_FILE);
+
+        let mut storage = FileStorage::open(&dir, 0).unwrap();
+        storage.commit(&add(&outputs[0])).unwrap();
+
+        //an append fails after writing part of its entry, and the log cannot be cut
+        //right away either since the handle is read-only
+        storage.log = File::open(&log_path).unwrap();
+        let mut torn = OpenOptions::new().append(true).open(&log_path).unwrap();
+        torn.write_all(&[codec::WIRE_VERSION, 200, 0, 0, 0, 1, 2])
+            .unwrap();
+        assert!(storage.commit(&add(&outputs[1])).is_err());
+        assert!(storage.torn);
+
+        //the next commit cuts the torn bytes before appending
+        storage.log = OpenOptions::new().write(true).open(&log_path).unwrap();
+        storage.commit(&add(&outputs[2])).unwrap();
+        drop(storage);
+
+        let ledger = Ledger::open(FileStorage::open(&dir, 0).unwrap()).unwrap();
+        let expected: HashSet<OutputId> = [&outputs[0], &outputs[2]]
+            .iter()
+            .map(|x| output_id(&x.one_time_account))
+            .collect();
+        assert_eq!(output_ids(&ledger), expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_storage_refused_batch_test() {
+        let dir = temp_dir("refused_batch");
+        let (issuer, to) = (Account::new(), Account::new());
+        let address = to.to_address(Network::Testnet);
+        let mint = |amount| {
+            Record::AddTransaction(Transaction::Mint(Box::new(
+                MintTx::new(&issuer, &address, amount).unwrap(),
+            )))
+        };
+
+        let mut storage = FileStorage::open(&dir, 0).unwrap();
+        storage.commit(&[mint(u64::MAX)]).unwrap();
+        let log_len = storage.log_len;
+        //a batch the state refuses never reaches the log
+        assert_eq!(storage.commit(&[mint(1)]), Err(LedgerError::SupplyOverflow));
+        assert_eq!(storage.log_len, log_len);
+        assert_eq!(
+            fs::metadata(dir.join(FileStorage::LOG_FILE)).unwrap().len(),
+            log_len
+        );
+        drop(storage);
+
+        let storage = FileStorage::open(&dir, 0).unwrap();
+        assert_eq!(storage.supply(), u64::MAX);
+        assert_eq!(storage.sequence, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_storage_snapshot_test() {
+        let dir = temp_dir("snapshot");
+        let account = Account::new();
+
+        let mut ledger = Ledger::open(FileStorage::open(&dir, 2).unwrap()).unwrap();
+        for amount in 1..=5 {
+            ledger.add_output(mint(&account, amount)).unwrap();
+        }
+        let expected = output_ids(&ledger);
+        drop(ledger);
+
+        //four entries went into snapshots, only the fifth is still in the log
+        assert!(dir.join(FileStorage::SNAPSHOT_FILE).exists());
+        let storage = FileStorage::open(&dir, 2).unwrap();
+        assert_eq!(storage.sequence, 5);
+        assert_eq!(storage.since_snapshot, 1);
+        let ledger = Ledger::open(storage).unwrap();
+        assert_eq!(output_ids(&ledger), expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
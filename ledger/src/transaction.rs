@@ -0,0 +1,143 @@
+use crate::error::LedgerError;
+use crate::ledger::{output_id, KeyImage, OutputId};
+use ct_account::confidential_transaction::{ConfidentialTransaction, SignTx};
+use ct_account::mint_transaction::MintTx;
+use ct_account::ring_confidential_transaction::RingSignature;
+use ct_account::TxHash;
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+
+//either transaction kind, as stored on disk and later carried in blocks
+#[derive(Clone)]
+pub enum Transaction {
+    Sign(Box<SignTx>),
+    Ring(Box<RingSignature>),
+    Mint(Box<MintTx>),
+}
+
+impl Transaction {
+    const SIGN_TAG: u8 = 0;
+    const RING_TAG: u8 = 1;
+    const MINT_TAG: u8 = 2;
+
+    pub fn txid(&self) -> TxHash {
+        match self {
+            Transaction::Sign(tx) => tx.txid(),
+            Transaction::Ring(tx) => tx.txid(),
+            Transaction::Mint(tx) => tx.txid(),
+        }
+    }
+
+    //plaintext fee collected by the block producer, mints carry none
+    pub fn fee(&self) -> u64 {
+        match self {
+            Transaction::Sign(tx) => tx.fee,
+            Transaction::Ring(tx) => tx.fee,
+            Transaction::Mint(_) => 0,
+        }
+    }
+
+    //public amount added to the supply, only mints create any
+    pub fn minted(&self) -> u64 {
+        match self {
+            Transaction::Mint(tx) => tx.amount,
+            Transaction::Sign(_) | Transaction::Ring(_) => 0,
+        }
+    }
+
+    pub fn outputs(&self) -> &[ConfidentialTransaction] {
+        match self {
+            Transaction::Sign(tx) => &tx.outputs,
+            Transaction::Ring(tx) => &tx.outputs,
+            Transaction::Mint(tx) => std::slice::from_ref(&tx.output),
+        }
+    }
+
+    //outputs removed from the ledger, only a `SignTx` reveals its real inputs
+    pub fn spent_outputs(&self) -> Vec<OutputId> {
+        match self {
+            Transaction::Sign(tx) => tx
+                .inputs
+                .iter()
+                .map(|x| output_id(&x.one_time_account))
+                .collect(),
+            Transaction::Ring(_) | Transaction::Mint(_) => Vec::new(),
+        }
+    }
+
+    //key images burned by the transaction. Every spend burns the key image of its
+    //real input, whether it reveals the input or hides it in a ring, so the two
+    //kinds cannot spend one output twice.
+    pub fn key_images(&self) -> Result<Vec<KeyImage>, LedgerError> {
+        match self {
+            Transaction::Sign(tx) => Ok(Self::sign_key_images(tx)),
+            Transaction::Ring(tx) => Self::ring_key_images(tx),
+            Transaction::Mint(_) => Ok(Vec::new()),
+        }
+    }
+
+    //one key image per input, their proof is checked with the signatures
+    pub(crate) fn sign_key_images(tx: &SignTx) -> Vec<KeyImage> {
+        tx.key_images()
+            .iter()
+            .map(|x| x.compress().to_bytes())
+            .collect()
+    }
+
+    //one key image per input column, the trailing commitment column has a fresh
+    //random key every time and does not link anything
+    pub(crate) fn ring_key_images(tx: &RingSignature) -> Result<Vec<KeyImage>, LedgerError> {
+        let input_size = tx.inputs.first().map(|x| x.len()).unwrap_or(0);
+        if input_size == 0
+            || tx.inputs.iter().any(|x| x.len() != input_size)
+            || tx.sig.key_images.len() != input_size + 1
+        {
+            return Err(LedgerError::MalformedRing);
+        }
+        Ok(tx.sig.key_images[..input_size]
+            .iter()
+            .map(|x| x.compress().to_bytes())
+            .collect())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Transaction, LedgerError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
+}
+
+impl Encode for Transaction {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            Transaction::Sign(tx) => {
+                writer.write_u8(Self::SIGN_TAG);
+                tx.encode(writer);
+            }
+            Transaction::Ring(tx) => {
+                writer.write_u8(Self::RING_TAG);
+                tx.encode(writer);
+            }
+            Transaction::Mint(tx) => {
+                writer.write_u8(Self::MINT_TAG);
+                tx.encode(writer);
+            }
+        }
+    }
+}
+
+impl Decode for Transaction {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        match reader.read_u8()? {
+            Self::SIGN_TAG => Ok(Transaction::Sign(Box::new(SignTx::decode(reader)?))),
+            Self::RING_TAG => Ok(Transaction::Ring(Box::new(RingSignature::decode(reader)?))),
+            Self::MINT_TAG => Ok(Transaction::Mint(Box::new(MintTx::decode(reader)?))),
+            tag => Err(UtilsError::UnknownTag {
+                kind: "transaction",
+                tag,
+            }),
+        }
+    }
+}
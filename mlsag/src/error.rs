@@ -0,0 +1,37 @@
+use ct_utils::error::UtilsError;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MlsagError {
+    Codec(UtilsError),
+    SignerIndexOutOfRing { index: usize, ring_size: usize },
+    //every ring member needs as many keys as the signer, and at least one
+    RingWidthMismatch,
+}
+
+impl fmt::Display for MlsagError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MlsagError::Codec(e) => write!(f, "{}", e),
+            MlsagError::SignerIndexOutOfRing { index, ring_size } => {
+                write!(f, "signer index {} outside a ring of {}", index, ring_size)
+            }
+            MlsagError::RingWidthMismatch => write!(f, "ring members differ in width"),
+        }
+    }
+}
+
+impl std::error::Error for MlsagError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MlsagError::Codec(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<UtilsError> for MlsagError {
+    fn from(e: UtilsError) -> MlsagError {
+        MlsagError::Codec(e)
+    }
+}
@@ -1,9 +1,11 @@
 use ct_utils::get_random_scalar;
 use curve25519_dalek::scalar::Scalar;
 
+pub mod error;
 pub mod material;
 pub mod mlsag;
 pub mod signature;
+mod transcript;
 
 pub fn get_random_scalars(size: usize) -> Vec<Scalar> {
     let mut scalars: Vec<Scalar> = Vec::with_capacity(size);
@@ -16,9 +18,11 @@ pub fn get_random_scalars(size: usize) -> Vec<Scalar> {
 #[cfg(test)]
 mod tests {
     use crate::{
+        error::MlsagError,
         get_random_scalars,
         material::{KeyPair, Material},
         mlsag::Mlsag,
+        signature::Signarute,
     };
     use ct_utils::generator::BASEPOINT_G2;
     use curve25519_dalek::scalar::Scalar;
@@ -35,8 +39,100 @@ mod tests {
             signer: singer_material,
             decoys: decoys_material,
         };
-        let sig = mlsag.sign(msg);
-        assert!(sig.verify(msg))
+        let sig = mlsag.sign_at(msg, 2).unwrap();
+        let ring = mlsag.ring_at(2).unwrap();
+        assert!(sig.verify(msg, &ring));
+
+        let decoded = Signarute::from_bytes(&sig.to_bytes()).unwrap();
+        assert_eq!(decoded, sig);
+        assert!(decoded.verify(msg, &ring));
+
+        //the ring comes from the verifier, swapping in a key the signer does not
+        //know breaks the signature
+        let mut forged = ring.clone();
+        forged[2][0] = Scalar::from(7u64) * (*BASEPOINT_G2);
+        assert!(!sig.verify(msg, &forged));
+        assert!(!sig.verify(msg, &ring[..5]));
+        let mut narrow = ring;
+        narrow[0].pop();
+        assert!(!sig.verify(msg, &narrow));
+    }
+
+    #[test]
+    fn mlsag_signer_index_test() {
+        let msg = b"hello world";
+        let mlsag = Mlsag {
+            signer: generator_singer_material(2),
+            decoys: generator_decoys_material(3, 2),
+        };
+        let signer_keys: Vec<_> = mlsag
+            .signer
+            .key_pairs
+            .iter()
+            .map(|x| x.public_key)
+            .collect();
+
+        //the ring closes wherever the signer sits, and only in that arrangement
+        for index in 0..4 {
+            let sig = mlsag.sign_at(msg, index).unwrap();
+            let ring = mlsag.ring_at(index).unwrap();
+            assert_eq!(ring[index], signer_keys);
+            assert!(sig.verify(msg, &ring));
+            assert!(!sig.verify(b"another message", &ring));
+            assert!(!sig.verify(msg, &mlsag.ring_at((index + 1) % 4).unwrap()));
+        }
+
+        //a ring with the signer alone still closes
+        let alone = Mlsag {
+            signer: generator_singer_material(2),
+            decoys: Vec::new(),
+        };
+        assert!(alone
+            .sign(msg)
+            .unwrap()
+            .verify(msg, &alone.ring_at(0).unwrap()));
+
+        assert_eq!(
+            mlsag.sign_at(msg, 4).err(),
+            Some(MlsagError::SignerIndexOutOfRing {
+                index: 4,
+                ring_size: 4
+            })
+        );
+        let narrow = Mlsag {
+            signer: generator_singer_material(1),
+            decoys: generator_decoys_material(3, 2),
+        };
+        assert_eq!(narrow.sign(msg).err(), Some(MlsagError::RingWidthMismatch));
+    }
+
+    #[test]
+    fn mlsag_signer_position_uniform_test() {
+        let msg = b"hello world";
+        let ring_size = 4;
+        let trials = 400;
+        let mlsag = Mlsag {
+            signer: generator_singer_material(1),
+            decoys: generator_decoys_material(ring_size - 1, 1),
+        };
+        let rings: Vec<_> = (0..ring_size).map(|x| mlsag.ring_at(x).unwrap()).collect();
+
+        //the signature hides the position, it verifies against one arrangement only
+        let mut counts = vec![0usize; ring_size];
+        for _ in 0..trials {
+            let sig = mlsag.sign(msg).unwrap();
+            let index = rings.iter().position(|x| sig.verify(msg, x)).unwrap();
+            counts[index] += 1;
+        }
+
+        //chi-squared with 3 degrees of freedom, 24 is exceeded with probability
+        //below 1/30000 when positions are uniform
+        let expected = (trials / ring_size) as f64;
+        let chi_squared: f64 = counts
+            .iter()
+            .map(|x| (*x as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_squared < 24.0, "signer positions {:?}", counts);
     }
 
     fn generator_singer_material(size: usize) -> Material {
@@ -1,12 +1,14 @@
-use ct_utils::{generator::BASEPOINT_G2, transcript::TranscriptProtocol};
+use ct_utils::generator::BASEPOINT_G2;
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
-use merlin::Transcript;
 
-use crate::get_random_scalars;
+use crate::{get_random_scalars, transcript};
 
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyPair {
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
     pub public_key: RistrettoPoint,
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
     pub private_key: Scalar,
 }
 
@@ -30,8 +32,8 @@ impl Material {
     pub fn new_signer(key_pairs: Vec<KeyPair>) -> Self {
         let alpha_vec = get_random_scalars(key_pairs.len());
         Material {
-            key_pairs: key_pairs,
-            alpha_vec: alpha_vec,
+            key_pairs,
+            alpha_vec,
             s_vec: Vec::default(),
         }
     }
@@ -39,9 +41,9 @@ impl Material {
     pub fn new_decoys(key_pairs: Vec<KeyPair>) -> Self {
         let s_vec = get_random_scalars(key_pairs.len());
         Material {
-            key_pairs: key_pairs,
+            key_pairs,
             alpha_vec: Vec::default(),
-            s_vec: s_vec,
+            s_vec,
         }
     }
 
@@ -53,13 +55,12 @@ impl Material {
     }
 
     pub fn compute_signer_challenge(&self, msg: &[u8]) -> Scalar {
-        let mut transcript = Transcript::new(b"mlsag");
-        transcript.append_message(b"msg", msg);
+        let mut transcript = transcript::new(msg);
 
         for (key, alpha) in self.key_pairs.iter().zip(self.alpha_vec.iter()) {
-            transcript.append_scalar_mul_point(b"L", alpha, &BASEPOINT_G2);
+            transcript.append_scalar_mul_point(transcript::L_LABEL, alpha, &BASEPOINT_G2);
             transcript.append_scalar_mul_point(
-                b"R",
+                transcript::R_LABEL,
                 alpha,
                 &RistrettoPoint::hash_from_bytes::<sha3::Sha3_512>(
                     key.public_key.compress().as_bytes(),
@@ -67,17 +68,16 @@ impl Material {
             );
         }
 
-        transcript.challenge_scalar(b"")
+        transcript.challenge_scalar(transcript::CHALLENGE_LABEL)
     }
 
     pub fn compute_decoy_challenge(
         &self,
         msg: &[u8],
         c_pai: &Scalar,
-        key_images: &Vec<RistrettoPoint>,
+        key_images: &[RistrettoPoint],
     ) -> Scalar {
-        let mut transcript = Transcript::new(b"mlsag");
-        transcript.append_message(b"msg", msg);
+        let mut transcript = transcript::new(msg);
 
         for ((key, s), key_image) in self
             .key_pairs
@@ -86,23 +86,23 @@ impl Material {
             .zip(key_images.iter())
         {
             transcript.append_double_scalar_mul_point(
-                b"L",
+                transcript::L_LABEL,
                 (s, c_pai),
                 (&BASEPOINT_G2, &key.public_key),
             );
             transcript.append_double_scalar_mul_point(
-                b"R",
+                transcript::R_LABEL,
                 (s, c_pai),
                 (
                     &RistrettoPoint::hash_from_bytes::<sha3::Sha3_512>(
                         key.public_key.compress().as_bytes(),
                     ),
-                    &key_image,
+                    key_image,
                 ),
             );
         }
 
-        transcript.challenge_scalar(b"")
+        transcript.challenge_scalar(transcript::CHALLENGE_LABEL)
     }
 
     pub fn compute_signer_s_vec(&self, c_pai: &Scalar) -> Vec<Scalar> {
@@ -1,7 +1,9 @@
 use crate::{
+    error::MlsagError,
     material::{KeyPair, Material},
     signature::Signarute,
 };
+use ct_utils::get_random_index;
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
 
 #[derive(Default)]
@@ -19,32 +21,92 @@ impl Mlsag {
         self.decoys.push(Material::new_decoys(key_pairs))
     }
 
-    pub fn sign(&self, msg: &[u8]) -> Signarute {
+    //signs with the signer at a uniformly random column, see `sign_at`
+    pub fn sign(&self, msg: &[u8]) -> Result<Signarute, MlsagError> {
+        let ring_size = self.decoys.len() + 1;
+        self.sign_at(msg, get_random_index(ring_size).unwrap_or_default())
+    }
+
+    //the ring has the decoys in order with the signer's column inserted at
+    //`signer_index`. The challenge chain starts right after the signer, goes round
+    //the decoys and is closed at the signer, `c` is the challenge of column 0.
+    pub fn sign_at(&self, msg: &[u8], signer_index: usize) -> Result<Signarute, MlsagError> {
         let member_size = self.decoys.len() + 1;
+        if signer_index >= member_size {
+            return Err(MlsagError::SignerIndexOutOfRing {
+                index: signer_index,
+                ring_size: member_size,
+            });
+        }
+        let width = self.signer.key_pairs.len();
+        if width == 0
+            || self.signer.alpha_vec.len() != width
+            || self
+                .decoys
+                .iter()
+                .any(|x| x.key_pairs.len() != width || x.s_vec.len() != width)
+        {
+            return Err(MlsagError::RingWidthMismatch);
+        }
         let key_images = self.compute_key_images();
-        let mut c_pai = self.compute_signer_challenge(msg);
 
-        let mut c_vec = Vec::with_capacity(member_size);
-        c_vec.push(c_pai);
-        for decoy in self.decoys.iter() {
-            c_pai = decoy.compute_decoy_challenge(msg, &c_pai, &key_images);
-            c_vec.push(c_pai);
+        let mut c_vec = vec![Scalar::default(); member_size];
+        let mut c_pai = self.compute_signer_challenge(msg);
+        for j in signer_index + 1..signer_index + member_size {
+            let column = j % member_size;
+            c_vec[column] = c_pai;
+            c_pai = self.decoy_at(signer_index, column).compute_decoy_challenge(
+                msg,
+                &c_pai,
+                &key_images,
+            );
         }
+        c_vec[signer_index] = c_pai;
 
-        let mut s_vec = Vec::with_capacity(member_size);
-        let mut public_key_vec: Vec<Vec<RistrettoPoint>> = Vec::with_capacity(member_size);
-        for s in self.decoys.iter() {
-            s_vec.push(s.s_vec.clone());
-            public_key_vec.push(s.key_pairs.iter().map(|x| x.public_key).collect());
-        }
-        s_vec.push(self.compute_signer_s_vec(&c_pai));
-        public_key_vec.push(self.signer.key_pairs.iter().map(|x| x.public_key).collect());
+        let s_vec = (0..member_size)
+            .map(|column| {
+                if column == signer_index {
+                    self.compute_signer_s_vec(&c_pai)
+                } else {
+                    self.decoy_at(signer_index, column).s_vec.clone()
+                }
+            })
+            .collect();
 
-        Signarute {
-            public_keys: public_key_vec,
-            key_images: key_images,
+        Ok(Signarute {
+            key_images,
             c: c_vec[0],
             s: s_vec,
+        })
+    }
+
+    //the ring `sign_at` signs over, which the verifier has to rebuild on its own
+    pub fn ring_at(&self, signer_index: usize) -> Result<Vec<Vec<RistrettoPoint>>, MlsagError> {
+        let ring_size = self.decoys.len() + 1;
+        if signer_index >= ring_size {
+            return Err(MlsagError::SignerIndexOutOfRing {
+                index: signer_index,
+                ring_size,
+            });
+        }
+        Ok((0..ring_size)
+            .map(|column| {
+                let material = if column == signer_index {
+                    &self.signer
+                } else {
+                    self.decoy_at(signer_index, column)
+                };
+                material.key_pairs.iter().map(|x| x.public_key).collect()
+            })
+            .collect())
+    }
+
+    //the decoy in ring column `column`, which is not the signer's
+    fn decoy_at(&self, signer_index: usize, column: usize) -> &Material {
+        if column < signer_index {
+            &self.decoys[column]
+        } else {
+            &self.decoys[column - 1]
         }
     }
 }
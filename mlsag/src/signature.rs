@@ -1,40 +1,91 @@
-use ct_utils::{generator::BASEPOINT_G2, transcript::TranscriptProtocol};
+use crate::error::MlsagError;
+use crate::transcript;
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+use ct_utils::generator::BASEPOINT_G2;
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
-use merlin::Transcript;
 
+//the ring itself is not part of the signature, the verifier must derive it from
+//what is being spent so a signature can never bring its own keys
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Signarute {
-    pub public_keys: Vec<Vec<RistrettoPoint>>,
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::vec"))]
     pub key_images: Vec<RistrettoPoint>,
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
     pub c: Scalar,
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::matrix"))]
     pub s: Vec<Vec<Scalar>>,
 }
 
 impl Signarute {
-    pub fn verify(&self, msg: &[u8]) -> bool {
-        assert!(self.s.len() > 1 && self.public_keys.len() > 1);
-        let mut c_pai = self.c.clone();
-        for (s_vec, pk_vec) in self.s.iter().zip(self.public_keys.iter()) {
-            let mut transcript = Transcript::new(b"mlsag");
-            transcript.append_message(b"msg", msg);
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Signarute, MlsagError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
+
+    //`ring` holds one column of public keys per ring member, in signing order. Every
+    //column must have one key per key image and one response per key.
+    pub fn verify(&self, msg: &[u8], ring: &[Vec<RistrettoPoint>]) -> bool {
+        let width = self.key_images.len();
+        if ring.is_empty()
+            || width == 0
+            || ring.len() != self.s.len()
+            || ring
+                .iter()
+                .zip(self.s.iter())
+                .any(|(pk_vec, s_vec)| pk_vec.len() != width || s_vec.len() != width)
+        {
+            return false;
+        }
+
+        let mut c_pai = self.c;
+        for (s_vec, pk_vec) in self.s.iter().zip(ring.iter()) {
+            let mut transcript = transcript::new(msg);
 
             for ((pk, s), key_image) in pk_vec.iter().zip(s_vec.iter()).zip(self.key_images.iter())
             {
-                transcript.append_double_scalar_mul_point(b"L", (s, &c_pai), (&BASEPOINT_G2, &pk));
                 transcript.append_double_scalar_mul_point(
-                    b"R",
+                    transcript::L_LABEL,
+                    (s, &c_pai),
+                    (&BASEPOINT_G2, pk),
+                );
+                transcript.append_double_scalar_mul_point(
+                    transcript::R_LABEL,
                     (s, &c_pai),
                     (
                         &RistrettoPoint::hash_from_bytes::<sha3::Sha3_512>(
                             pk.compress().as_bytes(),
                         ),
-                        &key_image,
+                        key_image,
                     ),
                 );
             }
 
-            c_pai = transcript.challenge_scalar(b"");
+            c_pai = transcript.challenge_scalar(transcript::CHALLENGE_LABEL);
         }
 
         c_pai == self.c
     }
 }
+
+impl Encode for Signarute {
+    fn encode(&self, writer: &mut Writer) {
+        self.key_images.encode(writer);
+        self.c.encode(writer);
+        self.s.encode(writer);
+    }
+}
+
+impl Decode for Signarute {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(Signarute {
+            key_images: Vec::decode(reader)?,
+            c: Scalar::decode(reader)?,
+            s: Vec::decode(reader)?,
+        })
+    }
+}
@@ -0,0 +1,15 @@
+use ct_utils::transcript::{Label, ProofTranscript};
+
+//signing and verifying must build the ring challenges from the same labels
+pub(crate) const L_LABEL: Label = Label::new(b"L");
+pub(crate) const R_LABEL: Label = Label::new(b"R");
+pub(crate) const CHALLENGE_LABEL: Label = Label::new(b"challenge");
+const MLSAG_LABEL: Label = Label::new(b"mlsag");
+const MSG_LABEL: Label = Label::new(b"msg");
+
+//the transcript every ring member's challenge starts from
+pub(crate) fn new(msg: &[u8]) -> ProofTranscript {
+    let mut transcript = ProofTranscript::new(MLSAG_LABEL);
+    transcript.append_message(MSG_LABEL, msg);
+    transcript
+}
@@ -1,9 +1,7 @@
 use colored::*;
 use ct_account::account::Account;
+use ct_account::builder::TransactionBuilder;
 use ct_account::confidential_transaction::ConfidentialTransaction;
-use ct_account::ring_confidential_transaction::RingCT;
-use ct_token::prove::NonnegativeProof;
-use ct_token::token::*;
 use rand::prelude::*;
 
 fn main() {
@@ -30,7 +28,7 @@ fn main() {
         1,
         100,
     );
-    let mut output_amount = Vec::with_capacity(output_size as usize);
+    let mut output_amount = Vec::with_capacity(output_size);
     loop {
         for i in 1..output_size + 1 {
             output_amount.push(wait_for_number(
@@ -55,54 +53,33 @@ fn main() {
 
     let input_tx: Vec<ConfidentialTransaction> = input_amount
         .iter()
-        .map(|x| mint(&user_account[0], *x as u64))
+        .map(|x| mint(&user_account[0], *x))
         .collect();
 
-    let decoys_account = &user_account[1..ring_size as usize];
+    let decoys_account = &user_account[1..ring_size];
     let mut rng = rand::thread_rng();
-    let decoys: Vec<Vec<ConfidentialTransaction>> = decoys_account
-        .iter()
-        .map(|x| {
-            let mut v = Vec::with_capacity(input_size);
-            for _ in 0..input_size {
-                v.push(mint(x, rng.gen_range(1..10000)));
-            }
-            v
-        })
-        .collect();
+    let mut decoys = Vec::with_capacity(decoys_account.len() * input_size);
+    for x in decoys_account {
+        for _ in 0..input_size {
+            decoys.push(mint(x, rng.gen_range(1..10000)));
+        }
+    }
 
     let output_account = &user_account[ring_size..ring_size + output_size];
-    let output_account: Vec<(Account, u64)> = output_account
-        .iter()
-        .zip(output_amount.iter())
-        .map(|(x, y)| (x.clone(), *y))
-        .collect();
-
-    let ring_ct = RingCT {
-        ownership_account: user_account[0].clone(),
-        output_account: output_account,
-        input_tx: input_tx,
-        decoys: decoys,
-    };
+    let mut builder = TransactionBuilder::new()
+        .inputs(&input_tx, &user_account[0])
+        .decoys(&decoys, ring_size);
+    for (x, y) in output_account.iter().zip(output_amount.iter()) {
+        builder = builder.recipient(x, *y);
+    }
 
-    let mut ring_sig = ring_ct.transfer();
+    let ring_sig = builder.build_ring_signature().unwrap();
     println!("转账完成，开始验证交易\n");
-    assert!(ring_sig.verify());
+    ring_sig.verify().unwrap();
 }
 
 fn mint(admin: &Account, amount: u64) -> ConfidentialTransaction {
-    let (one_time_account, blind_pair, symmetric_key) = admin.gen_one_time_account();
-    let (token, token_secrt) = Token::mint(amount);
-    let encrypto_token_secret = token_secrt.encrypt(&symmetric_key).unwrap();
-    let nonnegative_proof = NonnegativeProof::new(&token_secrt);
-
-    ConfidentialTransaction {
-        one_time_account: one_time_account,
-        blind_point: blind_pair.get_blind_point(),
-        token: token,
-        nonnegative_proof: nonnegative_proof,
-        encrypto_token_secret: encrypto_token_secret,
-    }
+    ConfidentialTransaction::new(admin, amount).unwrap()
 }
 
 fn println_green(m: &str) {
@@ -0,0 +1,162 @@
+use crate::error::TokenError;
+use crate::prove::{OpeningProof, SumProof};
+use crate::token::Token;
+use ct_crypto::signature::{self, SignMsg};
+use ct_utils::transcript::TxContext;
+use ct_utils::{generator::BASEPOINT_G2, get_random_scalar};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{IsIdentity, VartimeMultiscalarMul};
+
+//collects the signatures of any number of transactions and checks them together.
+//Every signature equation h*P + s*G2 - R = 0 is scaled by a fresh random weight
+//and all of them are summed into one multiscalar multiplication, so an invalid
+//equation cannot be cancelled by another. Sum proofs and opening proofs are
+//signatures under keys derived from the commitments and are batched the same way.
+//Range proofs are not batched: bulletproofs keeps its verification equation
+//private, so callers verify them on their own. MLSAG rings chain their challenges
+//through hashes and cannot be folded in either.
+#[derive(Default)]
+pub struct BatchVerifier<'a> {
+    items: Vec<Item<'a>>,
+}
+
+struct Item<'a> {
+    public_key: RistrettoPoint,
+    sign: &'a SignMsg,
+    msg: Vec<u8>,
+}
+
+impl<'a> Item<'a> {
+    fn verify(&self) -> bool {
+        signature::verify(self.public_key, self.sign, &self.msg)
+    }
+}
+
+impl<'a> BatchVerifier<'a> {
+    pub fn new() -> BatchVerifier<'a> {
+        BatchVerifier::default()
+    }
+
+    pub fn add_signature(&mut self, public_key: RistrettoPoint, sign: &'a SignMsg, msg: &[u8]) {
+        self.items.push(Item {
+            public_key,
+            sign,
+            msg: msg.to_vec(),
+        });
+    }
+
+    //see `SumProof::verify_sum_proof`, a count mismatch is reported right away
+    pub fn add_sum_proof(
+        &mut self,
+        proof: &'a SumProof,
+        inputs: &[Token],
+        outputs: &[Token],
+        fee: u64,
+        context: &TxContext,
+    ) -> Result<(), TokenError> {
+        let (public_key, msg) = proof.statement(inputs, outputs, fee, context)?;
+        self.add_signature(public_key, proof.signature(), &msg);
+        Ok(())
+    }
+
+    pub fn add_opening_proof(
+        &mut self,
+        proof: &'a OpeningProof,
+        token: &Token,
+        balance: u64,
+        msg: &[u8],
+    ) {
+        self.add_signature(OpeningProof::key(token, balance), proof.signature(), msg);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    //on failure every item is checked on its own to find the first invalid one
+    pub fn verify(&self) -> Result<(), TokenError> {
+        if self.batch_holds() {
+            return Ok(());
+        }
+        match self.items.iter().position(|x| !x.verify()) {
+            Some(index) => Err(TokenError::InvalidBatchItem(index)),
+            None => Ok(()),
+        }
+    }
+
+    fn batch_holds(&self) -> bool {
+        let mut scalars = Vec::with_capacity(self.items.len() * 2 + 1);
+        let mut points = Vec::with_capacity(self.items.len() * 2 + 1);
+        let mut g2_scalar = Scalar::zero();
+        for item in &self.items {
+            let weight = get_random_scalar();
+            let blind_point = item.sign.blind_point();
+            scalars.push(weight * signature::challenge(&item.public_key, &blind_point, &item.msg));
+            points.push(item.public_key);
+            scalars.push(-weight);
+            points.push(blind_point);
+            g2_scalar += weight * item.sign.private_key_blind();
+        }
+        scalars.push(g2_scalar);
+        points.push(*BASEPOINT_G2);
+        RistrettoPoint::vartime_multiscalar_mul(&scalars, &points).is_identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::TokenSecret;
+
+    #[test]
+    fn batch_verify_test() {
+        let context = TxContext::default();
+        let keys: Vec<Scalar> = (0..3).map(|_| get_random_scalar()).collect();
+        let signs: Vec<SignMsg> = keys.iter().map(|x| signature::sign(*x, b"msg")).collect();
+        let (inputs, input_secrets): (Vec<Token>, Vec<TokenSecret>) =
+            [60, 40].iter().map(|x| Token::mint(*x)).unzip();
+        let (outputs, output_secrets): (Vec<Token>, Vec<TokenSecret>) =
+            [70, 25].iter().map(|x| Token::mint(*x)).unzip();
+        let sum_proof =
+            SumProof::new_sum_proof(&input_secrets, &output_secrets, 5, &context).unwrap();
+        let opening_proof = OpeningProof::new(&input_secrets[0], b"open");
+
+        let mut batch = BatchVerifier::new();
+        assert!(batch.verify().is_ok());
+        for (key, sign) in keys.iter().zip(signs.iter()) {
+            batch.add_signature(key * (*BASEPOINT_G2), sign, b"msg");
+        }
+        batch
+            .add_sum_proof(&sum_proof, &inputs, &outputs, 5, &context)
+            .unwrap();
+        batch.add_opening_proof(&opening_proof, &inputs[0], 60, b"open");
+        assert_eq!(batch.len(), 5);
+        assert!(batch.verify().is_ok());
+
+        //each kind of item is located when it is the invalid one
+        let mut bad = BatchVerifier::new();
+        bad.add_signature(keys[0] * (*BASEPOINT_G2), &signs[0], b"msg");
+        bad.add_signature(keys[1] * (*BASEPOINT_G2), &signs[1], b"other msg");
+        bad.add_signature(keys[2] * (*BASEPOINT_G2), &signs[0], b"msg");
+        assert_eq!(bad.verify(), Err(TokenError::InvalidBatchItem(1)));
+
+        let mut bad = BatchVerifier::new();
+        bad.add_signature(keys[0] * (*BASEPOINT_G2), &signs[0], b"msg");
+        bad.add_sum_proof(&sum_proof, &inputs, &outputs, 6, &context)
+            .unwrap();
+        assert_eq!(bad.verify(), Err(TokenError::InvalidBatchItem(1)));
+        assert_eq!(
+            bad.add_sum_proof(&sum_proof, &inputs[..1], &outputs, 5, &context),
+            Err(TokenError::SumProofCountMismatch)
+        );
+
+        let mut bad = BatchVerifier::new();
+        bad.add_opening_proof(&opening_proof, &inputs[0], 61, b"open");
+        assert_eq!(bad.verify(), Err(TokenError::InvalidBatchItem(0)));
+    }
+}
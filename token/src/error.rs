@@ -0,0 +1,66 @@
+use crate::prove::RangeBits;
+use ct_crypto::error::CryptoError;
+use ct_utils::error::UtilsError;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+    Codec(UtilsError),
+    Crypto(CryptoError),
+    //the range proof could not be built
+    RangeProof,
+    //the amount does not fit in the range proof's bit width
+    AmountOutOfRange(RangeBits),
+    //an aggregate range proof covers 1 to `MAX_AGGREGATED_OUTPUTS` outputs
+    RangeProofCountOutOfRange,
+    //a sum proof needs at least one input and one output, at most u32::MAX each
+    SumProofCountOutOfRange,
+    SumProofCountMismatch,
+    Unbalanced,
+    BalanceOverflow,
+    //index of the first invalid item in a `BatchVerifier`, in the order added
+    InvalidBatchItem(usize),
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenError::Codec(e) => write!(f, "{}", e),
+            TokenError::Crypto(e) => write!(f, "{}", e),
+            TokenError::RangeProof => write!(f, "cannot prove the balance is in range"),
+            TokenError::AmountOutOfRange(bits) => {
+                write!(f, "amount does not fit in {} bits", bits.bits())
+            }
+            TokenError::RangeProofCountOutOfRange => {
+                write!(f, "aggregate range proof count out of range")
+            }
+            TokenError::SumProofCountOutOfRange => write!(f, "sum proof count out of range"),
+            TokenError::SumProofCountMismatch => write!(f, "sum proof count mismatch"),
+            TokenError::Unbalanced => write!(f, "inputs and outputs do not balance"),
+            TokenError::BalanceOverflow => write!(f, "balance overflow"),
+            TokenError::InvalidBatchItem(index) => write!(f, "invalid batch item {}", index),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TokenError::Codec(e) => Some(e),
+            TokenError::Crypto(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<UtilsError> for TokenError {
+    fn from(e: UtilsError) -> TokenError {
+        TokenError::Codec(e)
+    }
+}
+
+impl From<CryptoError> for TokenError {
+    fn from(e: CryptoError) -> TokenError {
+        TokenError::Crypto(e)
+    }
+}
@@ -1,2 +1,7 @@
+#[macro_use]
+extern crate lazy_static;
+
+pub mod batch;
+pub mod error;
 pub mod prove;
 pub mod token;
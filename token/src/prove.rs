@@ -1,203 +1,648 @@
 use super::token::*;
+use crate::error::TokenError;
 use bulletproofs::{BulletproofGens, PedersenGens, RangeProof};
-use ct_utils::{
-    generator::BASEPOINT_G1, generator::BASEPOINT_G2, get_random_scalar,
-    point_to_bytes, transcript::TranscriptProtocol,
-};
-use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar, traits::MultiscalarMul};
-use merlin::Transcript;
+use ct_crypto::signature::{self, SignMsg};
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+use ct_utils::generator::BASEPOINT_G1;
+use ct_utils::transcript::{Label, ProofTranscript, TxContext};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::{scalar::Scalar, traits::Identity, traits::MultiscalarMul};
 
+//largest number of outputs one `AggregateRangeProof` can cover
+pub const MAX_AGGREGATED_OUTPUTS: usize = 64;
+
+//the number of bits a range proof covers, only amounts below 2^bits can be proven.
+//The width is not in the proof, the verifier has to expect the one it was made for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeBits {
+    Bits8,
+    Bits16,
+    Bits32,
+    Bits64,
+}
+
+impl RangeBits {
+    pub fn bits(self) -> usize {
+        match self {
+            RangeBits::Bits8 => 8,
+            RangeBits::Bits16 => 16,
+            RangeBits::Bits32 => 32,
+            RangeBits::Bits64 => 64,
+        }
+    }
+
+    pub fn max_amount(self) -> u64 {
+        u64::MAX >> (64 - self.bits())
+    }
+
+    //bulletproofs only looks at the low bits of a value, an amount that does not
+    //fit would give a proof that fails to verify instead of an error
+    fn check(self, amount: u64) -> Result<(), TokenError> {
+        if amount > self.max_amount() {
+            return Err(TokenError::AmountOutOfRange(self));
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    //protocol constants shared by every range proof, the Pedersen generators are
+    //G1 for the value and G2 for the blind
+    static ref PC_GENS: PedersenGens = PedersenGens::default();
+    static ref BP_GENS: BulletproofGens = BulletproofGens::new(64, MAX_AGGREGATED_OUTPUTS);
+}
+
+//shows sum(inputs) = sum(outputs) + fee for any number of inputs and outputs.
+//When the values balance, the difference of the commitments minus fee*G1 is
+//excess*G2 with excess the difference of the blinds, so a signature under the
+//excess opens it. The proof is one signature whatever the counts, which are
+//kept only so a proof cannot be checked against a different shape of transaction.
+//The signed message binds the commitments, the fee and the `TxContext`.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SumProof {
-    theta_a: Scalar,
-    theta_b: Scalar,
-    theta_1: Scalar,
-    theta_2: Scalar,
-    theta_3: Scalar,
+    input_count: u32,
+    output_count: u32,
+    signature: SignMsg,
 }
 
 impl SumProof {
-    pub fn new_sum_proof(
-        input: &TokenSecret,
-        output1: &TokenSecret,
-        output2: &TokenSecret,
-    ) -> SumProof {
-        Self::prove_sum_relationship(
-            output1.balance,
-            output2.balance,
-            &output1.blind,
-            &output2.blind,
-            &input.blind,
-        )
+    const LABEL: Label = Label::new(b"sum_proof");
+    const INPUT_LABEL: Label = Label::new(b"input");
+    const OUTPUT_LABEL: Label = Label::new(b"output");
+    const FEE_LABEL: Label = Label::new(b"fee");
+    const MSG_LABEL: Label = Label::new(b"msg");
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
     }
 
-    fn prove_sum_relationship(
-        c1_value: u64,
-        c2_value: u64,
-        c1_blinding: &Scalar,
-        c2_blinding: &Scalar,
-        c3_blinding: &Scalar,
-    ) -> SumProof {
-        let alpha = get_random_scalar();
-        let beta = get_random_scalar();
-        let r1 = get_random_scalar();
-        let r2 = get_random_scalar();
-
-        let ca = RistrettoPoint::multiscalar_mul(
-            &[Scalar::from(c1_value), *c1_blinding],
-            &[*BASEPOINT_G1, *BASEPOINT_G2],
-        );
-        let cb = RistrettoPoint::multiscalar_mul(
-            &[Scalar::from(c2_value), *c2_blinding],
-            &[*BASEPOINT_G1, *BASEPOINT_G2],
-        );
-        let c = RistrettoPoint::multiscalar_mul(
-            &[Scalar::from(c1_value + c2_value), *c3_blinding],
-            &[*BASEPOINT_G1, *BASEPOINT_G2],
-        );
+    pub fn from_bytes(bytes: &[u8]) -> Result<SumProof, TokenError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
 
-        let mut transcript = Transcript::new(b"sum_proof");
-        transcript.append_message(b"",&point_to_bytes(&ca));
-        transcript.append_message(b"", &point_to_bytes(&cb));
-        transcript.append_message(b"", &point_to_bytes(&c));
-        let x = transcript.challenge_scalar(b"");
-
-        let theta_a = alpha - Scalar::from(c1_value) * x;
-        let theta_b = beta - Scalar::from(c2_value) * x;
-        let theta_1 = r1 - c1_blinding * x;
-        let theta_2 = r2 - c2_blinding * x;
-        let theta_3 = r1 + r2 - c3_blinding * x;
-
-        SumProof {
-            theta_a,
-            theta_b,
-            theta_1,
-            theta_2,
-            theta_3,
+    pub fn new_sum_proof(
+        inputs: &[TokenSecret],
+        outputs: &[TokenSecret],
+        fee: u64,
+        context: &TxContext,
+    ) -> Result<SumProof, TokenError> {
+        let input_count = Self::count(inputs.len())?;
+        let output_count = Self::count(outputs.len())?;
+        let input_balance = Self::total(inputs.iter().map(|x| x.balance))?;
+        let output_balance = Self::total(outputs.iter().map(|x| x.balance).chain([fee]))?;
+        if input_balance != output_balance {
+            return Err(TokenError::Unbalanced);
         }
+
+        let excess = inputs.iter().map(|x| x.blind).sum::<Scalar>()
+            - outputs.iter().map(|x| x.blind).sum::<Scalar>();
+        let input_tokens: Vec<Token> = inputs.iter().map(|x| x.get_token()).collect();
+        let output_tokens: Vec<Token> = outputs.iter().map(|x| x.get_token()).collect();
+        let msg = Self::message(&input_tokens, &output_tokens, fee, context);
+        Ok(SumProof {
+            input_count,
+            output_count,
+            signature: signature::sign(excess, &msg),
+        })
     }
 
+    //checks sum(inputs) = sum(outputs) + fee, the public fee is taken out of the
+    //input commitments as fee*G1 so the prover never handles it
     pub fn verify_sum_proof(
         &self,
-        input: &Token,
-        output1: &Token,
-        output2: &Token,
-    ) -> Result<bool, &'static str> {
-        self.verify_sum_relationship(
-            &output1.get_point(),
-            &output2.get_point(),
-            &input.get_point(),
-        )
+        inputs: &[Token],
+        outputs: &[Token],
+        fee: u64,
+        context: &TxContext,
+    ) -> Result<bool, TokenError> {
+        let (excess, msg) = self.statement(inputs, outputs, fee, context)?;
+        Ok(signature::verify(excess, &self.signature, &msg))
     }
 
-    fn verify_sum_relationship(
+    //the key `signature` must verify under and the message it signs
+    pub(crate) fn statement(
         &self,
-        c1_point: &RistrettoPoint,
-        c2_point: &RistrettoPoint,
-        c3_point: &RistrettoPoint,
-    ) -> Result<bool, &'static str> {
-        let mut transcript = Transcript::new(b"sum_proof");
-        transcript.append_message(b"",&point_to_bytes(&c1_point));
-        transcript.append_message(b"", &point_to_bytes(&c2_point));
-        transcript.append_message(b"", &point_to_bytes(&c3_point));
-        let x = transcript.challenge_scalar(b"");
+        inputs: &[Token],
+        outputs: &[Token],
+        fee: u64,
+        context: &TxContext,
+    ) -> Result<(RistrettoPoint, [u8; 32]), TokenError> {
+        if inputs.len() != self.input_count as usize || outputs.len() != self.output_count as usize
+        {
+            return Err(TokenError::SumProofCountMismatch);
+        }
+
+        let excess = inputs.iter().map(|x| x.get_point()).sum::<RistrettoPoint>()
+            - outputs
+                .iter()
+                .map(|x| x.get_point())
+                .sum::<RistrettoPoint>()
+            - RistrettoPoint::multiscalar_mul(&[Scalar::from(fee)], &[*BASEPOINT_G1]);
+        Ok((excess, Self::message(inputs, outputs, fee, context)))
+    }
+
+    pub(crate) fn signature(&self) -> &SignMsg {
+        &self.signature
+    }
+
+    //a transaction spends at least one input and creates at least one output
+    fn count(len: usize) -> Result<u32, TokenError> {
+        match u32::try_from(len) {
+            Ok(0) | Err(_) => Err(TokenError::SumProofCountOutOfRange),
+            Ok(count) => Ok(count),
+        }
+    }
+
+    fn total<I: Iterator<Item = u64>>(mut balances: I) -> Result<u64, TokenError> {
+        balances
+            .try_fold(0u64, |sum, x| sum.checked_add(x))
+            .ok_or(TokenError::BalanceOverflow)
+    }
+
+    fn message(inputs: &[Token], outputs: &[Token], fee: u64, context: &TxContext) -> [u8; 32] {
+        let mut transcript = ProofTranscript::with_context(Self::LABEL, context);
+        for x in inputs {
+            transcript.append_point(Self::INPUT_LABEL, &x.get_point());
+        }
+        for x in outputs {
+            transcript.append_point(Self::OUTPUT_LABEL, &x.get_point());
+        }
+        transcript.append_u64(Self::FEE_LABEL, fee);
+        let mut msg = [0u8; 32];
+        transcript.challenge_bytes(Self::MSG_LABEL, &mut msg);
+        msg
+    }
+}
+
+impl Encode for SumProof {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u32(self.input_count);
+        writer.write_u32(self.output_count);
+        self.signature.encode(writer);
+    }
+}
+
+impl Decode for SumProof {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(SumProof {
+            input_count: reader.read_u32()?,
+            output_count: reader.read_u32()?,
+            signature: SignMsg::decode(reader)?,
+        })
+    }
+}
 
-        let left = RistrettoPoint::multiscalar_mul(
-            &[self.theta_a + self.theta_b, self.theta_3],
-            &[*BASEPOINT_G1, *BASEPOINT_G2],
-        ) + RistrettoPoint::multiscalar_mul(&[x], &[*c3_point]);
+//shows a token commits to exactly `balance` without revealing the blind:
+//token - balance*G1 = blind*G2, so a signature under the blind that verifies
+//against that difference opens the commitment
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpeningProof(SignMsg);
 
-        let d1 = RistrettoPoint::multiscalar_mul(
-            &[self.theta_a, self.theta_1],
-            &[*BASEPOINT_G1, *BASEPOINT_G2],
-        ) + RistrettoPoint::multiscalar_mul(&[x], &[*c1_point]);
+impl OpeningProof {
+    pub fn new(secret: &TokenSecret, msg: &[u8]) -> OpeningProof {
+        OpeningProof(signature::sign(secret.blind, msg))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<OpeningProof, TokenError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
+
+    pub fn verify(&self, token: &Token, balance: u64, msg: &[u8]) -> bool {
+        signature::verify(Self::key(token, balance), &self.0, msg)
+    }
+
+    //the key the signature must verify under
+    pub(crate) fn key(token: &Token, balance: u64) -> RistrettoPoint {
+        token.get_point()
+            - RistrettoPoint::multiscalar_mul(&[Scalar::from(balance)], &[*BASEPOINT_G1])
+    }
+
+    pub(crate) fn signature(&self) -> &SignMsg {
+        &self.0
+    }
+}
 
-        let d2 = RistrettoPoint::multiscalar_mul(
-            &[self.theta_b, self.theta_2],
-            &[*BASEPOINT_G1, *BASEPOINT_G2],
-        ) + RistrettoPoint::multiscalar_mul(&[x], &[*c2_point]);
-        let right = d1 + d2;
+impl Encode for OpeningProof {
+    fn encode(&self, writer: &mut Writer) {
+        self.0.encode(writer);
+    }
+}
 
-        Ok(left == right)
+impl Decode for OpeningProof {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(OpeningProof(SignMsg::decode(reader)?))
     }
 }
 
+//only verifies under the `TxContext` it was made with
 #[derive(Clone)]
 pub struct NonnegativeProof {
-    pc_gens: PedersenGens,
-    bp_gens: BulletproofGens,
     proof: RangeProof,
 }
 
 impl NonnegativeProof {
-    const LABEL: &'static [u8] = b"nonnegative proof";
+    const LABEL: Label = Label::new(b"nonnegative_proof");
 
     //use bulletproof algorithms to generate nonnegative proof
-    pub fn new(secret: &TokenSecret) -> NonnegativeProof {
-        let pc_gens = PedersenGens::default();
-        let bp_gens = BulletproofGens::new(64, 1);
-        let mut transcript = Transcript::new(Self::LABEL);
+    pub fn new(
+        secret: &TokenSecret,
+        bits: RangeBits,
+        context: &TxContext,
+    ) -> Result<NonnegativeProof, TokenError> {
+        bits.check(secret.balance)?;
+        let mut transcript = ProofTranscript::with_context(Self::LABEL, context);
         let (proof, _) = RangeProof::prove_single(
-            &bp_gens,
-            &pc_gens,
-            &mut transcript,
+            &BP_GENS,
+            &PC_GENS,
+            transcript.as_merlin(),
             secret.balance,
             &secret.blind,
-            32,
+            bits.bits(),
         )
-        .expect("generate nonnegative proof error");
+        .map_err(|_| TokenError::RangeProof)?;
 
-        NonnegativeProof {
-            pc_gens,
-            bp_gens,
-            proof,
-        }
+        Ok(NonnegativeProof { proof })
+    }
+
+    //only the range proof is serialized, the generators are protocol constants
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<NonnegativeProof, TokenError> {
+        Ok(codec::from_bytes(bytes)?)
     }
 
     //verify nonnegative proof
-    pub fn verify(&mut self, token: &Token) -> bool {
-        let mut transcript = Transcript::new(Self::LABEL);
+    pub fn verify(&self, token: &Token, bits: RangeBits, context: &TxContext) -> bool {
+        let mut transcript = ProofTranscript::with_context(Self::LABEL, context);
         self.proof
             .verify_single(
-                &self.bp_gens,
-                &self.pc_gens,
-                &mut transcript,
+                &BP_GENS,
+                &PC_GENS,
+                transcript.as_merlin(),
                 &token.get_point().compress(),
-                32,
+                bits.bits(),
+            )
+            .is_ok()
+    }
+}
+
+impl Encode for NonnegativeProof {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_bytes(&self.proof.to_bytes());
+    }
+}
+
+impl Decode for NonnegativeProof {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(NonnegativeProof {
+            proof: decode_range_proof(reader)?,
+        })
+    }
+}
+
+//serialized as the hex of the bare range proof, like the binary encoding
+#[cfg(feature = "serde")]
+impl serde::Serialize for NonnegativeProof {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ct_utils::serde_hex::bytes::serialize(&self.proof.to_bytes(), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for NonnegativeProof {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let bytes = ct_utils::serde_hex::bytes::deserialize(deserializer)?;
+        let proof = RangeProof::from_bytes(&bytes).map_err(D::Error::custom)?;
+        Ok(NonnegativeProof { proof })
+    }
+}
+
+//one range proof for all outputs of a transaction, logarithmic in their number.
+//Bulletproofs aggregate a power of two of values, so the commitments are padded
+//with commitments to zero under a zero blind, which the verifier adds back itself.
+//Like `NonnegativeProof` it is bound to a `TxContext`.
+#[derive(Clone)]
+pub struct AggregateRangeProof {
+    proof: RangeProof,
+}
+
+impl AggregateRangeProof {
+    const LABEL: Label = Label::new(b"aggregate_range_proof");
+
+    pub fn new(
+        secrets: &[TokenSecret],
+        bits: RangeBits,
+        context: &TxContext,
+    ) -> Result<AggregateRangeProof, TokenError> {
+        let padded_len = Self::padded_len(secrets.len())?;
+        for x in secrets {
+            bits.check(x.balance)?;
+        }
+        let mut values: Vec<u64> = secrets.iter().map(|x| x.balance).collect();
+        let mut blinds: Vec<Scalar> = secrets.iter().map(|x| x.blind).collect();
+        values.resize(padded_len, 0);
+        blinds.resize(padded_len, Scalar::zero());
+
+        let mut transcript = ProofTranscript::with_context(Self::LABEL, context);
+        let (proof, _) = RangeProof::prove_multiple(
+            &BP_GENS,
+            &PC_GENS,
+            transcript.as_merlin(),
+            &values,
+            &blinds,
+            bits.bits(),
+        )
+        .map_err(|_| TokenError::RangeProof)?;
+        Ok(AggregateRangeProof { proof })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<AggregateRangeProof, TokenError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
+
+    //every token in `tokens`, in the order they were proven
+    pub fn verify(&self, tokens: &[Token], bits: RangeBits, context: &TxContext) -> bool {
+        let padded_len = match Self::padded_len(tokens.len()) {
+            Ok(len) => len,
+            Err(_) => return false,
+        };
+        let mut commitments: Vec<CompressedRistretto> =
+            tokens.iter().map(|x| x.get_point().compress()).collect();
+        commitments.resize(padded_len, RistrettoPoint::identity().compress());
+
+        let mut transcript = ProofTranscript::with_context(Self::LABEL, context);
+        self.proof
+            .verify_multiple(
+                &BP_GENS,
+                &PC_GENS,
+                transcript.as_merlin(),
+                &commitments,
+                bits.bits(),
             )
             .is_ok()
     }
+
+    fn padded_len(len: usize) -> Result<usize, TokenError> {
+        if len == 0 || len > MAX_AGGREGATED_OUTPUTS {
+            return Err(TokenError::RangeProofCountOutOfRange);
+        }
+        Ok(len.next_power_of_two())
+    }
+}
+
+impl Encode for AggregateRangeProof {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_bytes(&self.proof.to_bytes());
+    }
+}
+
+impl Decode for AggregateRangeProof {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(AggregateRangeProof {
+            proof: decode_range_proof(reader)?,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for AggregateRangeProof {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ct_utils::serde_hex::bytes::serialize(&self.proof.to_bytes(), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for AggregateRangeProof {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let bytes = ct_utils::serde_hex::bytes::deserialize(deserializer)?;
+        let proof = RangeProof::from_bytes(&bytes).map_err(D::Error::custom)?;
+        Ok(AggregateRangeProof { proof })
+    }
+}
+
+fn decode_range_proof(reader: &mut Reader) -> Result<RangeProof, UtilsError> {
+    RangeProof::from_bytes(&reader.read_bytes()?).map_err(|_| UtilsError::Malformed("range proof"))
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use ct_utils::transcript::{ChainId, Label};
+
+    const TX: Label = Label::new(b"tx");
+
+    //proofs made for one transaction or chain do not verify for another
+    fn other_contexts() -> [TxContext; 3] {
+        [
+            TxContext::default().bind(TX, b"other tx"),
+            TxContext::new(ChainId::TESTNET),
+            TxContext::new(ChainId::TESTNET).bind(TX, b"tx"),
+        ]
+    }
+
     #[test]
     fn sum_proof_test() {
+        let context = TxContext::default();
         let (input, input_secret) = Token::mint(100);
         let (output1, output_secret_1) = Token::mint(90);
         let (output2, output_secret_2) = Token::mint(10);
 
-        let proof = SumProof::new_sum_proof(&input_secret, &output_secret_1, &output_secret_2);
-        let verify = proof.verify_sum_proof(&input, &output1, &output2);
-        assert_eq!(true, verify.unwrap());
+        let outputs = [output1, output2];
+        let output_secrets = [output_secret_1.clone(), output_secret_2];
+        let proof = SumProof::new_sum_proof(
+            std::slice::from_ref(&input_secret),
+            &output_secrets,
+            0,
+            &context,
+        )
+        .unwrap();
+        assert!(proof
+            .verify_sum_proof(&[input], &outputs, 0, &context)
+            .unwrap());
+        for other in other_contexts() {
+            assert!(!proof
+                .verify_sum_proof(&[input], &outputs, 0, &other)
+                .unwrap());
+        }
+        assert_eq!(SumProof::from_bytes(&proof.to_bytes()).unwrap(), proof);
+        assert!(!proof
+            .verify_sum_proof(&[input], &[output2, output1], 0, &context)
+            .unwrap());
+
+        //the fee is the part of the input not covered by the outputs
+        let (output2, output_secret_2) = Token::mint(4);
+        let output_secrets = [output_secret_1.clone(), output_secret_2];
+        let proof = SumProof::new_sum_proof(
+            std::slice::from_ref(&input_secret),
+            &output_secrets,
+            6,
+            &context,
+        )
+        .unwrap();
+        assert!(proof
+            .verify_sum_proof(&[input], &[output1, output2], 6, &context)
+            .unwrap());
+        assert!(!proof
+            .verify_sum_proof(&[input], &[output1, output2], 5, &context)
+            .unwrap());
+
+        let (_, output_secret_2) = Token::mint(110);
+        assert_eq!(
+            SumProof::new_sum_proof(
+                std::slice::from_ref(&input_secret),
+                &[output_secret_1, output_secret_2],
+                0,
+                &context
+            ),
+            Err(TokenError::Unbalanced)
+        );
+        assert_eq!(
+            SumProof::new_sum_proof(&[], &[], 0, &context),
+            Err(TokenError::SumProofCountOutOfRange)
+        );
+    }
+
+    #[test]
+    fn sum_proof_many_test() {
+        let context = TxContext::default();
+        let inputs: Vec<(Token, TokenSecret)> =
+            [30, 50, 20].iter().map(|x| Token::mint(*x)).collect();
+        let outputs: Vec<(Token, TokenSecret)> =
+            [10, 25, 35, 27].iter().map(|x| Token::mint(*x)).collect();
+        let (input_tokens, input_secrets): (Vec<Token>, Vec<TokenSecret>) =
+            inputs.into_iter().unzip();
+        let (output_tokens, output_secrets): (Vec<Token>, Vec<TokenSecret>) =
+            outputs.into_iter().unzip();
+
+        let proof = SumProof::new_sum_proof(&input_secrets, &output_secrets, 3, &context).unwrap();
+        assert!(proof
+            .verify_sum_proof(&input_tokens, &output_tokens, 3, &context)
+            .unwrap());
+        //the size does not depend on the counts
+        let (_, small_input) = Token::mint(5);
+        let (_, small_output) = Token::mint(5);
+        let small = SumProof::new_sum_proof(&[small_input], &[small_output], 0, &context).unwrap();
+        assert_eq!(small.to_bytes().len(), proof.to_bytes().len());
+
+        //a token dropped or added is a clean error, not a panic
+        assert_eq!(
+            proof.verify_sum_proof(&input_tokens, &output_tokens[..3], 3, &context),
+            Err(TokenError::SumProofCountMismatch)
+        );
+        assert_eq!(
+            proof.verify_sum_proof(&input_tokens[1..], &output_tokens, 3, &context),
+            Err(TokenError::SumProofCountMismatch)
+        );
+    }
+
+    #[test]
+    fn opening_proof_test() {
+        let (token, secret) = Token::mint(20);
+        let proof = OpeningProof::new(&secret, b"mint");
+        assert!(proof.verify(&token, 20, b"mint"));
+        assert!(!proof.verify(&token, 21, b"mint"));
+        assert!(!proof.verify(&token, 20, b"other"));
 
-        let (output2, output_secret_2) = Token::mint(110);
-        let proof = SumProof::new_sum_proof(&input_secret, &output_secret_1, &output_secret_2);
-        let verify = proof.verify_sum_proof(&input, &output1, &output2);
-        assert_eq!(false, verify.unwrap())
+        let decoded = OpeningProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert!(decoded.verify(&token, 20, b"mint"));
     }
 
     #[test]
     fn nonnegative_proof_test() {
+        let context = TxContext::default();
         let (token, secret) = Token::mint(20);
 
         let pc_gens = PedersenGens::default();
         let commitment = pc_gens.commit(Scalar::from(20u64), secret.blind);
         assert_eq!(token.get_point(), commitment);
 
-        let mut proof = NonnegativeProof::new(&secret);
-        let verify = proof.verify(&token);
-        assert!(verify)
+        let proof = NonnegativeProof::new(&secret, RangeBits::Bits32, &context).unwrap();
+        let verify = proof.verify(&token, RangeBits::Bits32, &context);
+        assert!(verify);
+
+        let bytes = proof.to_bytes();
+        let decoded = NonnegativeProof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert!(decoded.verify(&token, RangeBits::Bits32, &context));
+        for other in other_contexts() {
+            assert!(!proof.verify(&token, RangeBits::Bits32, &other));
+        }
+    }
+
+    #[test]
+    fn range_bits_test() {
+        let context = TxContext::default();
+        let (token, secret) = Token::mint(u64::MAX);
+        let proof = NonnegativeProof::new(&secret, RangeBits::Bits64, &context).unwrap();
+        assert!(proof.verify(&token, RangeBits::Bits64, &context));
+        //the verifier only accepts the width it expects
+        assert!(!proof.verify(&token, RangeBits::Bits32, &context));
+
+        for bits in [RangeBits::Bits8, RangeBits::Bits16, RangeBits::Bits32] {
+            let (token, secret) = Token::mint(bits.max_amount());
+            let proof = NonnegativeProof::new(&secret, bits, &context).unwrap();
+            assert!(proof.verify(&token, bits, &context));
+
+            let (_, secret) = Token::mint(bits.max_amount() + 1);
+            assert_eq!(
+                NonnegativeProof::new(&secret, bits, &context).err(),
+                Some(TokenError::AmountOutOfRange(bits))
+            );
+            assert_eq!(
+                AggregateRangeProof::new(&[secret], bits, &context).err(),
+                Some(TokenError::AmountOutOfRange(bits))
+            );
+        }
+    }
+
+    #[test]
+    fn aggregate_range_proof_test() {
+        let context = TxContext::default();
+        let (tokens, secrets): (Vec<Token>, Vec<TokenSecret>) =
+            [5, 0, u64::MAX].iter().map(|x| Token::mint(*x)).unzip();
+        let bits = RangeBits::Bits64;
+
+        //three outputs are padded to four
+        let proof = AggregateRangeProof::new(&secrets, bits, &context).unwrap();
+        assert!(proof.verify(&tokens, bits, &context));
+        assert!(!proof.verify(&tokens, RangeBits::Bits32, &context));
+        assert!(!proof.verify(&tokens[..2], bits, &context));
+        let mut swapped = tokens.clone();
+        swapped.swap(0, 2);
+        assert!(!proof.verify(&swapped, bits, &context));
+
+        let decoded = AggregateRangeProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert!(decoded.verify(&tokens, bits, &context));
+        for other in other_contexts() {
+            assert!(!proof.verify(&tokens, bits, &other));
+        }
+        //smaller than one proof per output
+        let single = NonnegativeProof::new(&secrets[0], bits, &context)
+            .unwrap()
+            .to_bytes()
+            .len();
+        assert!(proof.to_bytes().len() < single * 2);
+
+        assert!(matches!(
+            AggregateRangeProof::new(&[], bits, &context),
+            Err(TokenError::RangeProofCountOutOfRange)
+        ));
+        assert!(!proof.verify(&[], bits, &context));
     }
 }
@@ -1,17 +1,25 @@
+use crate::error::TokenError;
 use ct_crypto::aes;
-use ct_utils::read_integer;
+use ct_crypto::error::CryptoError;
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+use ct_utils::hash::Hasher;
 use ct_utils::{
-    bytes_to_scalar, generator::BASEPOINT_G1, generator::BASEPOINT_G2, get_random_scalar,
-    scalar_to_bytes,
+    domain_hash_to_scalar, generator::BASEPOINT_G1, generator::BASEPOINT_G2, get_random_scalar,
 };
 use curve25519_dalek::traits::MultiscalarMul;
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
 
-#[derive(PartialEq, Copy, Clone)]
-pub struct Token(RistrettoPoint);
+#[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token(
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))] RistrettoPoint,
+);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TokenSecret {
+    #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
     pub blind: Scalar,
     pub balance: u64,
 }
@@ -22,60 +30,197 @@ impl Token {
     }
 
     pub fn mint(balance: u64) -> (Token, TokenSecret) {
-        let balance_scalar = Scalar::from(balance);
-        let blind = get_random_scalar();
-        let commitment = RistrettoPoint::multiscalar_mul(
-            &[balance_scalar, blind],
-            &[*BASEPOINT_G1, *BASEPOINT_G2],
-        );
-        (Token(commitment), TokenSecret { blind, balance })
+        let secret = TokenSecret {
+            blind: get_random_scalar(),
+            balance,
+        };
+        (secret.get_token(), secret)
+    }
+
+    //the blind is derived from the ECDH symmetric key instead of drawn at random,
+    //so the output only has to carry the masked amount, see `TokenSecret::mask`
+    pub fn mint_compact(balance: u64, symmetric_key: &[u8]) -> (Token, TokenSecret) {
+        let secret = TokenSecret {
+            blind: TokenSecret::derive_blind(symmetric_key),
+            balance,
+        };
+        (secret.get_token(), secret)
+    }
+}
+
+impl Encode for Token {
+    fn encode(&self, writer: &mut Writer) {
+        self.0.encode(writer);
     }
 }
 
-#[derive(Clone)]
-pub struct EncryptoTokenSecret {
-    pub balance_crypto: Vec<u8>,
-    pub blind_crypto: Vec<u8>,
+impl Decode for Token {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(Token(RistrettoPoint::decode(reader)?))
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EncryptoTokenSecret {
+    //balance and blind sealed together with AES-256-GCM. The associated data binds
+    //them to the output's one-time account and commitment, so the ciphertext cannot
+    //be edited or moved to another output without failing to decrypt.
+    Sealed {
+        //fresh for every output
+        #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
+        nonce: [u8; aes::NONCE_SIZE],
+        #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::bytes"))]
+        ciphertext: Vec<u8>,
+    },
+    //only the amount xor a mask derived from the symmetric key, the blind is
+    //derived from the same key. The reopened commitment authenticates the amount.
+    Compact {
+        #[cfg_attr(feature = "serde", serde(with = "ct_utils::serde_hex::value"))]
+        masked_amount: [u8; 8],
+    },
 }
 
 impl TokenSecret {
-    //user symmetric key to encrypt balance and blind
-    pub fn encrypt(&self, symmetric_key: &Vec<u8>) -> Result<EncryptoTokenSecret, &'static str> {
-        let balance_crypto =
-            match aes::encrypt(&self.balance.to_ne_bytes().to_vec(), &symmetric_key) {
-                Ok(v) => v,
-                Err(_) => return Err("encrypt err"),
-            };
-
-        let blind_crypto =
-            match aes::encrypt(&scalar_to_bytes(&self.blind).to_vec(), &symmetric_key) {
-                Ok(v) => v,
-                Err(_) => return Err("encrypt err"),
-            };
-
-        Ok(EncryptoTokenSecret {
-            balance_crypto,
-            blind_crypto,
+    const BLIND_DOMAIN: &'static [u8] = b"ct_token_blind";
+
+    //the commitment balance*G1 + blind*G2 this secret opens
+    pub fn get_token(&self) -> Token {
+        Token(RistrettoPoint::multiscalar_mul(
+            &[Scalar::from(self.balance), self.blind],
+            &[*BASEPOINT_G1, *BASEPOINT_G2],
+        ))
+    }
+
+    //user symmetric key to encrypt balance and blind for the output at
+    //`one_time_account` that holds this secret's commitment
+    pub fn encrypt(
+        &self,
+        symmetric_key: &[u8],
+        one_time_account: &RistrettoPoint,
+    ) -> Result<EncryptoTokenSecret, TokenError> {
+        let mut writer = Writer::new();
+        writer.write_u64(self.balance);
+        self.blind.encode(&mut writer);
+        let nonce = aes::random_nonce();
+        let associated_data =
+            EncryptoTokenSecret::associated_data(one_time_account, &self.get_token());
+        Ok(EncryptoTokenSecret::Sealed {
+            nonce,
+            ciphertext: aes::encrypt(
+                &writer.into_bytes(),
+                symmetric_key,
+                &nonce,
+                &associated_data,
+            )?,
         })
     }
+
+    //compact form of a secret from `Token::mint_compact` under the same key
+    pub fn mask(&self, symmetric_key: &[u8]) -> EncryptoTokenSecret {
+        EncryptoTokenSecret::Compact {
+            masked_amount: xor_amount_mask(self.balance.to_le_bytes(), symmetric_key),
+        }
+    }
+
+    //the only place a blind is derived from a key, minting and decrypting both use it
+    fn derive_blind(symmetric_key: &[u8]) -> Scalar {
+        domain_hash_to_scalar(Self::BLIND_DOMAIN, symmetric_key)
+    }
+}
+
+//xor with the first 8 bytes of a key derived hash, applying it twice unmasks
+fn xor_amount_mask(mut amount: [u8; 8], symmetric_key: &[u8]) -> [u8; 8] {
+    let mask = Hasher::domain_sha_256(b"ct_token_amount", symmetric_key);
+    for (x, y) in amount.iter_mut().zip(mask.iter()) {
+        *x ^= y;
+    }
+    amount
 }
 
 impl EncryptoTokenSecret {
-    pub fn decrypt(&self, symmetric_key: &Vec<u8>) -> Result<TokenSecret, &'static str> {
-        let balance = match aes::decrypt(&self.balance_crypto, &symmetric_key) {
-            Ok(v) => read_integer::<u64>(&v),
-            Err(_) => return Err("decrypt err1"),
-        };
+    const LABEL: &'static [u8] = b"ct_token_secret";
+    const SEALED_TAG: u8 = 0;
+    const COMPACT_TAG: u8 = 1;
 
-        let blind = match aes::decrypt(&self.blind_crypto, &symmetric_key) {
-            Ok(v) => match bytes_to_scalar(&v) {
-                Ok(v) => v,
-                Err(_) => return Err("decrypt err2"),
-            },
-            Err(_) => return Err("decrypt err3"),
-        };
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(self)
+    }
 
-        Ok(TokenSecret { blind, balance })
+    pub fn from_bytes(bytes: &[u8]) -> Result<EncryptoTokenSecret, TokenError> {
+        Ok(codec::from_bytes(bytes)?)
+    }
+
+    //fails with `CryptoError::AuthenticationFailed` for a wrong key, a tampered
+    //ciphertext or one encrypted for another output
+    pub fn decrypt(
+        &self,
+        symmetric_key: &[u8],
+        one_time_account: &RistrettoPoint,
+        token: &Token,
+    ) -> Result<TokenSecret, TokenError> {
+        match self {
+            EncryptoTokenSecret::Sealed { nonce, ciphertext } => {
+                let associated_data = Self::associated_data(one_time_account, token);
+                let plaintext = aes::decrypt(ciphertext, symmetric_key, nonce, &associated_data)?;
+                let mut reader = Reader::new(&plaintext);
+                let balance = reader.read_u64()?;
+                let blind = Scalar::decode(&mut reader)?;
+                reader.finish()?;
+                Ok(TokenSecret { blind, balance })
+            }
+            EncryptoTokenSecret::Compact { masked_amount } => {
+                let amount = xor_amount_mask(*masked_amount, symmetric_key);
+                let (expect_token, secret) =
+                    Token::mint_compact(u64::from_le_bytes(amount), symmetric_key);
+                if expect_token != *token {
+                    return Err(CryptoError::AuthenticationFailed.into());
+                }
+                Ok(secret)
+            }
+        }
+    }
+
+    fn associated_data(one_time_account: &RistrettoPoint, token: &Token) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.write_raw(Self::LABEL);
+        one_time_account.encode(&mut writer);
+        token.encode(&mut writer);
+        writer.into_bytes()
+    }
+}
+
+impl Encode for EncryptoTokenSecret {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            EncryptoTokenSecret::Sealed { nonce, ciphertext } => {
+                writer.write_u8(Self::SEALED_TAG);
+                nonce.encode(writer);
+                writer.write_bytes(ciphertext);
+            }
+            EncryptoTokenSecret::Compact { masked_amount } => {
+                writer.write_u8(Self::COMPACT_TAG);
+                masked_amount.encode(writer);
+            }
+        }
+    }
+}
+
+impl Decode for EncryptoTokenSecret {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        match reader.read_u8()? {
+            Self::SEALED_TAG => Ok(EncryptoTokenSecret::Sealed {
+                nonce: reader.read_array()?,
+                ciphertext: reader.read_bytes()?,
+            }),
+            Self::COMPACT_TAG => Ok(EncryptoTokenSecret::Compact {
+                masked_amount: reader.read_array()?,
+            }),
+            tag => Err(UtilsError::UnknownTag {
+                kind: "token secret",
+                tag,
+            }),
+        }
     }
 }
 
@@ -85,12 +230,100 @@ mod tests {
     use super::*;
     #[test]
     fn mint_test() {
-        let (_, input_secret) = Token::mint(100);
+        let (token, input_secret) = Token::mint(100);
+        let one_time_account = get_random_scalar() * (*BASEPOINT_G2);
         let key: Vec<u8> = "abcdefghijklmnopqrstuvwxyzabcdef".as_bytes().to_vec();
-        let encry_input_secret = input_secret.encrypt(&key).unwrap();
-        let expect_secrt = encry_input_secret.decrypt(&key).unwrap();
+        let encry_input_secret = input_secret.encrypt(&key, &one_time_account).unwrap();
+        let expect_secrt = encry_input_secret
+            .decrypt(&key, &one_time_account, &token)
+            .unwrap();
         assert_eq!(expect_secrt.balance, input_secret.balance);
         assert_eq!(expect_secrt.blind, input_secret.blind);
+
+        let encoded = encry_input_secret.to_bytes();
+        assert_eq!(encoded.len(), 74);
+        let decoded = EncryptoTokenSecret::from_bytes(&encoded).unwrap();
+        assert_eq!(decoded, encry_input_secret);
+        //every encryption gets its own nonce
+        let again = input_secret.encrypt(&key, &one_time_account).unwrap();
+        assert_ne!(again, encry_input_secret);
+
+        let mut other_key = key.clone();
+        other_key[31] ^= 1;
+        let failed = Some(TokenError::Crypto(CryptoError::AuthenticationFailed));
+        assert_eq!(
+            encry_input_secret
+                .decrypt(&other_key, &one_time_account, &token)
+                .err(),
+            failed
+        );
+        assert_eq!(
+            input_secret.encrypt(&key[..8], &one_time_account).err(),
+            Some(TokenError::Crypto(CryptoError::InvalidKeyLength(8)))
+        );
+
+        //a tampered or misaddressed secret does not decrypt
+        let mut tampered = encry_input_secret.clone();
+        if let EncryptoTokenSecret::Sealed { ciphertext, .. } = &mut tampered {
+            ciphertext[0] ^= 1;
+        }
+        assert_eq!(
+            tampered.decrypt(&key, &one_time_account, &token).err(),
+            failed
+        );
+        let (other_token, _) = Token::mint(100);
+        assert_eq!(
+            encry_input_secret
+                .decrypt(&key, &one_time_account, &other_token)
+                .err(),
+            failed
+        );
+        let other_account = get_random_scalar() * (*BASEPOINT_G2);
+        assert_eq!(
+            encry_input_secret
+                .decrypt(&key, &other_account, &token)
+                .err(),
+            failed
+        );
+    }
+
+    #[test]
+    fn mint_compact_test() {
+        let key = Hasher::sha_256("shared secret");
+        let (token, secret) = Token::mint_compact(100, &key);
+        assert_eq!(Token::mint_compact(100, &key).0, token);
+        let compact = secret.mask(&key);
+        let encoded = compact.to_bytes();
+        assert_eq!(EncryptoTokenSecret::from_bytes(&encoded).unwrap(), compact);
+        //wire version, tag and masked amount against 74 bytes for a sealed secret
+        assert_eq!(encoded.len(), 10);
+
+        //the one-time account is not needed, the key is already bound to the output
+        let one_time_account = get_random_scalar() * (*BASEPOINT_G2);
+        let expect_secrt = compact.decrypt(&key, &one_time_account, &token).unwrap();
+        assert_eq!(expect_secrt.balance, 100);
+        assert_eq!(expect_secrt.blind, secret.blind);
+
+        let failed = Some(TokenError::Crypto(CryptoError::AuthenticationFailed));
+        let other_key = Hasher::sha_256("other secret");
+        assert_eq!(
+            compact.decrypt(&other_key, &one_time_account, &token).err(),
+            failed
+        );
+        let tampered = EncryptoTokenSecret::Compact {
+            masked_amount: xor_amount_mask(101u64.to_le_bytes(), &key),
+        };
+        assert_eq!(
+            tampered.decrypt(&key, &one_time_account, &token).err(),
+            failed
+        );
+        assert_eq!(
+            EncryptoTokenSecret::from_bytes(&[codec::WIRE_VERSION, 2]).err(),
+            Some(TokenError::Codec(UtilsError::UnknownTag {
+                kind: "token secret",
+                tag: 2
+            }))
+        );
     }
 }
 
@@ -0,0 +1,317 @@
+use crate::error::UtilsError;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+
+//every top-level encoding starts with this byte, bump it on any layout change
+pub const WIRE_VERSION: u8 = 1;
+
+pub trait Encode {
+    fn encode(&self, writer: &mut Writer);
+}
+
+pub trait Decode: Sized {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError>;
+}
+
+//version-tagged canonical encoding of a top-level value
+pub fn to_bytes<T: Encode>(value: &T) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.write_u8(WIRE_VERSION);
+    value.encode(&mut writer);
+    writer.into_bytes()
+}
+
+//inverse of `to_bytes`, rejects unknown versions and trailing bytes
+pub fn from_bytes<T: Decode>(bytes: &[u8]) -> Result<T, UtilsError> {
+    let mut reader = Reader::new(bytes);
+    let version = reader.read_u8()?;
+    if version != WIRE_VERSION {
+        return Err(UtilsError::UnsupportedWireVersion(version));
+    }
+    let value = T::decode(&mut reader)?;
+    reader.finish()?;
+    Ok(value)
+}
+
+#[derive(Default)]
+pub struct Writer(Vec<u8>);
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer(Vec::new())
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_len(&mut self, len: usize) {
+        self.write_u32(len as u32);
+    }
+
+    //length-prefixed byte string
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_len(bytes.len());
+        self.0.extend_from_slice(bytes);
+    }
+
+    pub fn write_raw(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn finish(&self) -> Result<(), UtilsError> {
+        if !self.data.is_empty() {
+            return Err(UtilsError::TrailingBytes);
+        }
+        Ok(())
+    }
+
+    pub fn read_raw(&mut self, len: usize) -> Result<&'a [u8], UtilsError> {
+        if self.data.len() < len {
+            return Err(UtilsError::UnexpectedEnd);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    //a fixed size array, the length is checked once in `read_raw`
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], UtilsError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.read_raw(N)?);
+        Ok(array)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, UtilsError> {
+        Ok(self.read_raw(1)?[0])
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, UtilsError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, UtilsError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    //a length prefix can never exceed the bytes left, which bounds allocations
+    pub fn read_len(&mut self) -> Result<usize, UtilsError> {
+        let len = self.read_u32()? as usize;
+        if len > self.remaining() {
+            return Err(UtilsError::LengthPrefixExceedsInput);
+        }
+        Ok(len)
+    }
+
+    pub fn read_bytes(&mut self) -> Result<Vec<u8>, UtilsError> {
+        let len = self.read_len()?;
+        Ok(self.read_raw(len)?.to_vec())
+    }
+}
+
+impl Encode for u64 {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u64(*self);
+    }
+}
+
+impl Decode for u64 {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        reader.read_u64()
+    }
+}
+
+//fixed size values such as txids and nonces, written without a length prefix
+impl<const N: usize> Encode for [u8; N] {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_raw(self);
+    }
+}
+
+impl<const N: usize> Decode for [u8; N] {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        reader.read_array()
+    }
+}
+
+impl Encode for RistrettoPoint {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_raw(self.compress().as_bytes());
+    }
+}
+
+impl Decode for RistrettoPoint {
+    //ristretto decompression only accepts the canonical encoding
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        CompressedRistretto(reader.read_array()?)
+            .decompress()
+            .ok_or(UtilsError::NonCanonicalPoint)
+    }
+}
+
+impl Encode for Scalar {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_raw(self.as_bytes());
+    }
+}
+
+impl Decode for Scalar {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        let bytes: [u8; 32] = reader.read_array()?;
+        Scalar::from_canonical_bytes(bytes).ok_or(UtilsError::NonCanonicalScalar)
+    }
+}
+
+//tag 0 for `None`, 1 followed by the value for `Some`
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, writer: &mut Writer) {
+        match self {
+            Some(value) => {
+                writer.write_u8(1);
+                value.encode(writer);
+            }
+            None => writer.write_u8(0),
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        match reader.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(reader)?)),
+            tag => Err(UtilsError::UnknownTag {
+                kind: "option",
+                tag,
+            }),
+        }
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_len(self.len());
+        for item in self {
+            item.encode(writer);
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, writer: &mut Writer) {
+        self.as_slice().encode(writer);
+    }
+}
+
+//the length prefix only bounds the element count by the bytes left, and one
+//encoded byte can stand for a 160-byte point or a whole nested Vec. So at most
+//this many bytes are reserved up front, the rest grows as items actually decode.
+const MAX_PREALLOCATION: usize = 64 * 1024;
+
+fn preallocation<T>(len: usize) -> usize {
+    len.min(MAX_PREALLOCATION / std::mem::size_of::<T>().max(1))
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        let len = reader.read_len()?;
+        let mut items = Vec::with_capacity(preallocation::<T>(len));
+        for _ in 0..len {
+            items.push(T::decode(reader)?);
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::BASEPOINT_G1;
+    use crate::get_random_scalar;
+
+    #[test]
+    fn roundtrip_test() {
+        let points = vec![*BASEPOINT_G1, get_random_scalar() * *BASEPOINT_G1];
+        let bytes = to_bytes(&points);
+        assert_eq!(bytes[0], WIRE_VERSION);
+        assert_eq!(from_bytes::<Vec<RistrettoPoint>>(&bytes).unwrap(), points);
+
+        let scalars = vec![vec![get_random_scalar()], vec![]];
+        let bytes = to_bytes(&scalars);
+        assert_eq!(from_bytes::<Vec<Vec<Scalar>>>(&bytes).unwrap(), scalars);
+
+        let options = vec![Some(7u64), None];
+        let bytes = to_bytes(&options);
+        assert_eq!(from_bytes::<Vec<Option<u64>>>(&bytes).unwrap(), options);
+    }
+
+    #[test]
+    fn reject_non_canonical_test() {
+        let mut bytes = to_bytes(&get_random_scalar());
+        bytes[1..].copy_from_slice(&[0xff; 32]);
+        assert!(from_bytes::<Scalar>(&bytes).is_err());
+
+        let mut bytes = to_bytes(&*BASEPOINT_G1);
+        bytes[1..].copy_from_slice(&[0xff; 32]);
+        assert!(from_bytes::<RistrettoPoint>(&bytes).is_err());
+
+        let mut bytes = to_bytes(&7u64);
+        bytes.push(0);
+        assert!(from_bytes::<u64>(&bytes).is_err());
+        bytes[0] = WIRE_VERSION + 1;
+        assert_eq!(
+            from_bytes::<u64>(&bytes[..9]),
+            Err(UtilsError::UnsupportedWireVersion(WIRE_VERSION + 1))
+        );
+
+        let mut writer = Writer::new();
+        writer.write_u8(WIRE_VERSION);
+        writer.write_u32(u32::MAX);
+        assert!(from_bytes::<Vec<u64>>(&writer.into_bytes()).is_err());
+    }
+
+    #[test]
+    fn preallocation_test() {
+        //a claimed length reserves a bounded number of bytes, whatever the item size
+        assert_eq!(preallocation::<u8>(100), 100);
+        let large = preallocation::<Vec<Vec<RistrettoPoint>>>(1 << 30);
+        assert!(large * std::mem::size_of::<Vec<Vec<RistrettoPoint>>>() <= MAX_PREALLOCATION);
+        assert_eq!(preallocation::<[u8; 0]>(1 << 30), MAX_PREALLOCATION);
+
+        //1000 bytes claiming 1000 nested Vecs decode 250 empty ones, then run out
+        let mut writer = Writer::new();
+        writer.write_u8(WIRE_VERSION);
+        writer.write_u32(1000);
+        writer.write_raw(&[0; 1000]);
+        assert_eq!(
+            from_bytes::<Vec<Vec<RistrettoPoint>>>(&writer.into_bytes()),
+            Err(UtilsError::UnexpectedEnd)
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt;
+
+//everything that can go wrong turning bytes into values, whether in the codec,
+//the hex adapters or the raw conversions in the crate root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UtilsError {
+    UnexpectedEnd,
+    TrailingBytes,
+    UnsupportedWireVersion(u8),
+    LengthPrefixExceedsInput,
+    InvalidLength { expected: usize, actual: usize },
+    NonCanonicalPoint,
+    NonCanonicalScalar,
+    InvalidHex,
+    //an enum tag no variant is encoded as, `kind` names the enum
+    UnknownTag { kind: &'static str, tag: u8 },
+    //well formed bytes that do not make a valid `kind`
+    Malformed(&'static str),
+}
+
+impl fmt::Display for UtilsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UtilsError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            UtilsError::TrailingBytes => write!(f, "trailing bytes"),
+            UtilsError::UnsupportedWireVersion(version) => {
+                write!(f, "unsupported wire version {}", version)
+            }
+            UtilsError::LengthPrefixExceedsInput => write!(f, "length prefix exceeds input"),
+            UtilsError::InvalidLength { expected, actual } => {
+                write!(f, "expected {} bytes, got {}", expected, actual)
+            }
+            UtilsError::NonCanonicalPoint => write!(f, "non-canonical point"),
+            UtilsError::NonCanonicalScalar => write!(f, "non-canonical scalar"),
+            UtilsError::InvalidHex => write!(f, "invalid hex"),
+            UtilsError::UnknownTag { kind, tag } => write!(f, "unknown {} tag {}", kind, tag),
+            UtilsError::Malformed(kind) => write!(f, "malformed {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for UtilsError {}
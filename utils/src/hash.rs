@@ -1,4 +1,4 @@
-use sha3::{Digest, Sha3_256};
+use sha3::{Digest, Sha3_256, Sha3_512};
 pub struct Hasher {}
 
 impl Hasher {
@@ -7,6 +7,26 @@ impl Hasher {
         hasher.update(data);
         hasher.finalize().to_vec()
     }
+
+    //the domain tag is length-prefixed so that no (domain, data) pair collides with another
+    pub fn domain_sha_256<T: AsRef<[u8]> + ?Sized>(domain: &[u8], data: &T) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update((domain.len() as u32).to_le_bytes());
+        hasher.update(domain);
+        hasher.update(data);
+        hasher.finalize().into()
+    }
+
+    //wide variant of `domain_sha_256`, enough bits to reduce into a uniform scalar
+    pub fn domain_sha_512<T: AsRef<[u8]> + ?Sized>(domain: &[u8], data: &T) -> [u8; 64] {
+        let mut hasher = Sha3_512::new();
+        hasher.update((domain.len() as u32).to_le_bytes());
+        hasher.update(domain);
+        hasher.update(data);
+        let mut digest = [0u8; 64];
+        digest.copy_from_slice(&hasher.finalize());
+        digest
+    }
 }
 
 #[cfg(test)]
@@ -22,4 +42,16 @@ mod tests {
         ];
         assert_eq!(result, r);
     }
+
+    #[test]
+    fn domain_sha_256_test() {
+        let a = Hasher::domain_sha_256(b"ab", b"c");
+        assert_ne!(a, Hasher::domain_sha_256(b"a", b"bc"));
+        assert_ne!(a.to_vec(), Hasher::sha_256("abc"));
+        assert_eq!(a, Hasher::domain_sha_256(b"ab", b"c"));
+
+        let wide = Hasher::domain_sha_512(b"ab", b"c");
+        assert_ne!(wide, Hasher::domain_sha_512(b"a", b"bc"));
+        assert_ne!(wide[..32], a);
+    }
 }
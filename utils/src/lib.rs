@@ -3,26 +3,31 @@ extern crate lazy_static;
 use curve25519_dalek::ristretto::CompressedRistretto;
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
 use rand_core::OsRng;
-use std::convert::TryInto;
 
+pub mod codec;
+pub mod error;
 pub mod generator;
 pub mod hash;
+pub mod merkle;
+#[cfg(feature = "serde")]
+pub mod serde_hex;
 pub mod transcript;
+use crate::error::UtilsError;
 use crate::hash::Hasher;
 
 pub trait ReadInteger<T> {
-    fn from_le_bytes(data: &[u8]) -> T;
-    fn from_be_bytes(data: &[u8]) -> T;
+    fn from_le_bytes(data: &[u8]) -> Result<T, UtilsError>;
+    fn from_be_bytes(data: &[u8]) -> Result<T, UtilsError>;
 }
 
 macro_rules! impl_read_integer {
     ($($t:ty),+) => {
         $(impl ReadInteger<$t> for $t {
-            fn from_le_bytes(data: &[u8]) -> $t {
-                <$t>::from_le_bytes(data.try_into().unwrap())
+            fn from_le_bytes(data: &[u8]) -> Result<$t, UtilsError> {
+                Ok(<$t>::from_le_bytes(to_array(data)?))
             }
-            fn from_be_bytes(data: &[u8]) -> $t {
-                <$t>::from_be_bytes(data.try_into().unwrap())
+            fn from_be_bytes(data: &[u8]) -> Result<$t, UtilsError> {
+                Ok(<$t>::from_be_bytes(to_array(data)?))
             }
         })+
     }
@@ -30,18 +35,22 @@ macro_rules! impl_read_integer {
 
 impl_read_integer!(u8, i16, i32, u32, i64, u64);
 
-pub fn read_integer<T: ReadInteger<T>>(data: &[u8]) -> T {
-    T::from_le_bytes(&data[..std::mem::size_of::<T>()])
+//reads a `T` from the start of `data`, which may be longer
+pub fn read_integer<T: ReadInteger<T>>(data: &[u8]) -> Result<T, UtilsError> {
+    let size = std::mem::size_of::<T>();
+    match data.get(..size) {
+        Some(data) => T::from_le_bytes(data),
+        None => Err(UtilsError::InvalidLength {
+            expected: size,
+            actual: data.len(),
+        }),
+    }
 }
 
-pub fn bytes_to_point(point: &[u8]) -> Result<RistrettoPoint, &'static str> {
-    let point_value = match CompressedRistretto::from_slice(&point).decompress() {
-        Some(v) => v,
-        None => {
-            return Err("bytes_to_point err");
-        }
-    };
-    Ok(point_value)
+pub fn bytes_to_point(point: &[u8]) -> Result<RistrettoPoint, UtilsError> {
+    CompressedRistretto(to_array(point)?)
+        .decompress()
+        .ok_or(UtilsError::NonCanonicalPoint)
 }
 
 pub fn point_to_bytes(point: &RistrettoPoint) -> Vec<u8> {
@@ -52,18 +61,15 @@ pub fn scalar_to_bytes(input: &Scalar) -> Vec<u8> {
     input.as_bytes().to_vec()
 }
 
-pub fn bytes_to_scalar(input: &[u8]) -> Result<Scalar, &'static str> {
-    let get_num_u8 = to_bytes32_slice(&input)?;
-    let scalar_num = Scalar::from_bits(*get_num_u8);
-    Ok(scalar_num)
+pub fn bytes_to_scalar(input: &[u8]) -> Result<Scalar, UtilsError> {
+    Ok(Scalar::from_bits(to_array(input)?))
 }
 
-fn to_bytes32_slice(barry: &[u8]) -> Result<&[u8; 32], &'static str> {
-    let pop_u8 = match barry.try_into() {
-        Ok(v) => v,
-        Err(_) => return Err("bytes_to_scalar err"),
-    };
-    Ok(pop_u8)
+fn to_array<const N: usize>(bytes: &[u8]) -> Result<[u8; N], UtilsError> {
+    bytes.try_into().map_err(|_| UtilsError::InvalidLength {
+        expected: N,
+        actual: bytes.len(),
+    })
 }
 
 pub fn hash_to_scalar<T: ?Sized + AsRef<[u8]>>(input: &T) -> Scalar {
@@ -72,13 +78,50 @@ pub fn hash_to_scalar<T: ?Sized + AsRef<[u8]>>(input: &T) -> Scalar {
     Scalar::from_bytes_mod_order(array)
 }
 
+//a 512-bit domain-separated hash reduced mod l, so the scalar is uniformly
+//distributed, unlike the single 256-bit reduction in `hash_to_scalar`
+pub fn domain_hash_to_scalar<T: ?Sized + AsRef<[u8]>>(domain: &[u8], input: &T) -> Scalar {
+    Scalar::from_bytes_mod_order_wide(&Hasher::domain_sha_512(domain, input))
+}
+
 pub fn get_random_scalar() -> Scalar {
     Scalar::random(&mut OsRng)
 }
 
+//uniform in 0..len, `None` for an empty range. Draws above the largest multiple
+//of len are rejected so no index is favoured.
+pub fn get_random_index(len: usize) -> Option<usize> {
+    use rand_core::RngCore;
+    if len == 0 {
+        return None;
+    }
+    let len = len as u64;
+    let zone = u64::MAX - (u64::MAX % len);
+    loop {
+        let x = OsRng.next_u64();
+        if x < zone {
+            return Some((x % len) as usize);
+        }
+    }
+}
+
 #[test]
 fn test() {
     let data = 100000u64;
     let data = data.to_ne_bytes().to_vec();
-    println!("{}", read_integer::<u64>(&data))
+    assert_eq!(read_integer::<u64>(&data), Ok(100000));
+    assert_eq!(
+        read_integer::<u64>(&data[..7]),
+        Err(UtilsError::InvalidLength {
+            expected: 8,
+            actual: 7
+        })
+    );
+    assert_eq!(
+        bytes_to_point(&[0; 31]).err(),
+        Some(UtilsError::InvalidLength {
+            expected: 32,
+            actual: 31
+        })
+    );
 }
@@ -0,0 +1,60 @@
+use crate::hash::Hasher;
+
+const LEAF_DOMAIN: &[u8] = b"ct_merkle_leaf";
+const NODE_DOMAIN: &[u8] = b"ct_merkle_node";
+const EMPTY_DOMAIN: &[u8] = b"ct_merkle_empty";
+
+//binary merkle root, leaves and inner nodes are hashed under different domains
+//so an inner node can never be passed off as a leaf. An odd node at the end of
+//a level is carried up unchanged instead of being paired with itself.
+pub fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    if leaves.is_empty() {
+        return Hasher::domain_sha_256(EMPTY_DOMAIN, b"");
+    }
+
+    let mut level: Vec<[u8; 32]> = leaves
+        .iter()
+        .map(|x| Hasher::domain_sha_256(LEAF_DOMAIN, x))
+        .collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => Hasher::domain_sha_256(NODE_DOMAIN, &[*left, *right].concat()),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merkle_root_test() {
+        let leaves: Vec<[u8; 32]> = (0..5u8).map(|x| [x; 32]).collect();
+        let root = merkle_root(&leaves);
+        assert_eq!(root, merkle_root(&leaves));
+        assert_ne!(root, merkle_root(&leaves[..4]));
+        assert_ne!(merkle_root(&[]), merkle_root(&[[0; 32]]));
+
+        let mut swapped = leaves.clone();
+        swapped.swap(0, 1);
+        assert_ne!(root, merkle_root(&swapped));
+
+        //an inner node is not a valid leaf
+        let node = Hasher::domain_sha_256(
+            NODE_DOMAIN,
+            &[
+                Hasher::domain_sha_256(LEAF_DOMAIN, &leaves[0]),
+                Hasher::domain_sha_256(LEAF_DOMAIN, &leaves[1]),
+            ]
+            .concat(),
+        );
+        assert_eq!(merkle_root(&leaves[..2]), node);
+        assert_ne!(merkle_root(&[node]), node);
+    }
+}
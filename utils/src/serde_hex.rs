@@ -0,0 +1,142 @@
+//serde adapters that write points, scalars and byte strings as lowercase hex,
+//reusing the canonical codec so JSON input gets the same checks as binary input
+use crate::codec::{Decode, Encode, Reader, Writer};
+use crate::error::UtilsError;
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::Serializer;
+
+fn encode_hex<T: Encode>(value: &T) -> String {
+    let mut writer = Writer::new();
+    value.encode(&mut writer);
+    hex::encode(writer.into_bytes())
+}
+
+fn decode_hex<T: Decode>(text: &str) -> Result<T, UtilsError> {
+    let bytes = hex::decode(text).map_err(|_| UtilsError::InvalidHex)?;
+    let mut reader = Reader::new(&bytes);
+    let value = T::decode(&mut reader)?;
+    reader.finish()?;
+    Ok(value)
+}
+
+//a single point or scalar as one hex string
+pub mod value {
+    use super::*;
+
+    pub fn serialize<T: Encode, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode_hex(value))
+    }
+
+    pub fn deserialize<'de, T: Decode, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        decode_hex(&text).map_err(D::Error::custom)
+    }
+}
+
+//a list of points or scalars as an array of hex strings
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<T: Encode, S: Serializer>(
+        values: &[T],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(encode_hex))
+    }
+
+    pub fn deserialize<'de, T: Decode, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<T>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|x| decode_hex(x).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+//a matrix of points or scalars as nested arrays of hex strings
+pub mod matrix {
+    use super::*;
+
+    pub fn serialize<T: Encode, S: Serializer>(
+        values: &[Vec<T>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            values
+                .iter()
+                .map(|row| row.iter().map(encode_hex).collect::<Vec<_>>()),
+        )
+    }
+
+    pub fn deserialize<'de, T: Decode, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<T>>, D::Error> {
+        Vec::<Vec<String>>::deserialize(deserializer)?
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|x| decode_hex(x).map_err(D::Error::custom))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+//an opaque byte string such as a ciphertext
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        hex::decode(text).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::BASEPOINT_G1;
+    use crate::get_random_scalar;
+    use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Sample {
+        #[serde(with = "super::value")]
+        point: RistrettoPoint,
+        #[serde(with = "super::vec")]
+        scalars: Vec<Scalar>,
+        #[serde(with = "super::matrix")]
+        points: Vec<Vec<RistrettoPoint>>,
+        #[serde(with = "super::bytes")]
+        data: Vec<u8>,
+    }
+
+    #[test]
+    fn json_roundtrip_test() {
+        let sample = Sample {
+            point: *BASEPOINT_G1,
+            scalars: vec![get_random_scalar()],
+            points: vec![vec![*BASEPOINT_G1], vec![]],
+            data: vec![0xde, 0xad],
+        };
+        let json = serde_json::to_string(&sample).unwrap();
+        assert!(json.contains("\"data\":\"dead\""));
+        assert_eq!(serde_json::from_str::<Sample>(&json).unwrap(), sample);
+
+        let bad = json.replace(
+            &hex::encode(BASEPOINT_G1.compress().as_bytes()),
+            &"ff".repeat(32),
+        );
+        assert!(serde_json::from_str::<Sample>(&bad).is_err());
+    }
+}
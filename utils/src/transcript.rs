@@ -1,48 +1,211 @@
+use crate::codec::{Decode, Encode, Reader, Writer};
+use crate::error::UtilsError;
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
 use merlin::Transcript;
 
-pub trait TranscriptProtocol {
-    fn append_scalar_mul_point(
-        &mut self,
-        label: &'static [u8],
-        scalar: &Scalar,
-        point: &RistrettoPoint,
-    );
+//every transcript starts with the protocol name and version, so a proof made
+//under other rules never verifies under these. Bump the version whenever the
+//contents of any transcript change.
+pub const PROTOCOL: &[u8] = b"confidential_transaction";
+pub const PROTOCOL_VERSION: u32 = 1;
 
-    fn append_double_scalar_mul_point(
-        &mut self,
-        label: &'static [u8],
-        scalars: (&Scalar, &Scalar),
-        points: (&RistrettoPoint, &RistrettoPoint),
-    );
+//a non-empty transcript label. `Label::new` is const, so a label declared as a
+//const is checked when the crate compiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(&'static [u8]);
+
+impl Label {
+    pub const fn new(label: &'static [u8]) -> Label {
+        assert!(!label.is_empty(), "transcript labels must not be empty");
+        Label(label)
+    }
+
+    pub fn as_bytes(&self) -> &'static [u8] {
+        self.0
+    }
+}
+
+//the chain a proof is valid on, a proof made for one chain fails on every other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct ChainId(pub u32);
+
+impl ChainId {
+    pub const MAINNET: ChainId = ChainId(1);
+    pub const TESTNET: ChainId = ChainId(2);
+}
 
-    fn challenge_scalar(&mut self, label: &'static [u8]) -> Scalar;
+impl Default for ChainId {
+    fn default() -> Self {
+        ChainId::MAINNET
+    }
+}
+
+impl Encode for ChainId {
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_u32(self.0);
+    }
+}
+
+impl Decode for ChainId {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(ChainId(reader.read_u32()?))
+    }
 }
 
-impl TranscriptProtocol for Transcript {
-    fn append_scalar_mul_point(
+//what a proof is bound to besides its own statement: the chain and whatever the
+//caller hooks in with `bind`, usually the transaction the proof belongs to. A
+//proof only verifies under the context it was made with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxContext {
+    chain_id: ChainId,
+    items: Vec<(Label, Vec<u8>)>,
+}
+
+impl TxContext {
+    pub fn new(chain_id: ChainId) -> TxContext {
+        TxContext {
+            chain_id,
+            items: Vec::new(),
+        }
+    }
+
+    pub fn chain_id(&self) -> ChainId {
+        self.chain_id
+    }
+
+    //appended to the transcript in the order bound
+    pub fn bind(mut self, label: Label, data: &[u8]) -> TxContext {
+        self.items.push((label, data.to_vec()));
+        self
+    }
+}
+
+const PROTOCOL_LABEL: Label = Label::new(b"protocol");
+const VERSION_LABEL: Label = Label::new(b"protocol_version");
+const PROOF_LABEL: Label = Label::new(b"proof");
+const CHAIN_ID_LABEL: Label = Label::new(b"chain_id");
+const CONTEXT_LABEL: Label = Label::new(b"context");
+
+//merlin transcript that only takes `Label`s and always starts with the protocol
+//header and the name of the proof
+pub struct ProofTranscript(Transcript);
+
+impl ProofTranscript {
+    //for proofs whose message already identifies the transaction, e.g. signatures
+    //over a prefix hash
+    pub fn new(proof: Label) -> ProofTranscript {
+        let mut transcript = Transcript::new(PROTOCOL_LABEL.as_bytes());
+        transcript.append_message(PROTOCOL_LABEL.as_bytes(), PROTOCOL);
+        transcript.append_u64(VERSION_LABEL.as_bytes(), PROTOCOL_VERSION as u64);
+        transcript.append_message(PROOF_LABEL.as_bytes(), proof.as_bytes());
+        ProofTranscript(transcript)
+    }
+
+    //for proofs that have to be tied to the chain and transaction they are made for
+    pub fn with_context(proof: Label, context: &TxContext) -> ProofTranscript {
+        let mut transcript = ProofTranscript::new(proof);
+        transcript.append_u64(CHAIN_ID_LABEL, context.chain_id.0 as u64);
+        transcript.append_u64(CONTEXT_LABEL, context.items.len() as u64);
+        for (label, data) in &context.items {
+            transcript.append_message(*label, data);
+        }
+        transcript
+    }
+
+    pub fn append_message(&mut self, label: Label, message: &[u8]) {
+        self.0.append_message(label.as_bytes(), message);
+    }
+
+    pub fn append_u64(&mut self, label: Label, value: u64) {
+        self.0.append_u64(label.as_bytes(), value);
+    }
+
+    pub fn append_point(&mut self, label: Label, point: &RistrettoPoint) {
+        self.0
+            .append_message(label.as_bytes(), point.compress().as_bytes());
+    }
+
+    pub fn append_scalar_mul_point(
         &mut self,
-        label: &'static [u8],
+        label: Label,
         scalar: &Scalar,
         point: &RistrettoPoint,
     ) {
-        let point = scalar * point;
-        self.append_message(label, point.compress().as_bytes());
+        self.append_point(label, &(scalar * point));
     }
 
-    fn append_double_scalar_mul_point(
+    pub fn append_double_scalar_mul_point(
         &mut self,
-        label: &'static [u8],
+        label: Label,
         scalars: (&Scalar, &Scalar),
         points: (&RistrettoPoint, &RistrettoPoint),
     ) {
-        let point = scalars.0 * points.0 + scalars.1 * points.1;
-        self.append_message(label, point.compress().as_bytes());
+        self.append_point(label, &(scalars.0 * points.0 + scalars.1 * points.1));
     }
 
-    fn challenge_scalar(&mut self, label: &'static [u8]) -> Scalar {
-        let mut buf = [0u8; 32];
-        self.challenge_bytes(label, &mut buf);
-        Scalar::from_bytes_mod_order(buf)
+    pub fn challenge_bytes(&mut self, label: Label, dest: &mut [u8]) {
+        self.0.challenge_bytes(label.as_bytes(), dest);
+    }
+
+    //reduced from 64 bytes so every scalar is equally likely
+    pub fn challenge_scalar(&mut self, label: Label) -> Scalar {
+        let mut buf = [0u8; 64];
+        self.0.challenge_bytes(label.as_bytes(), &mut buf);
+        Scalar::from_bytes_mod_order_wide(&buf)
+    }
+
+    //for libraries that drive a merlin transcript themselves, e.g. bulletproofs
+    pub fn as_merlin(&mut self) -> &mut Transcript {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROOF: Label = Label::new(b"test_proof");
+    const OTHER_PROOF: Label = Label::new(b"other_proof");
+    const MSG: Label = Label::new(b"msg");
+    const TX: Label = Label::new(b"tx");
+    const CHALLENGE: Label = Label::new(b"challenge");
+
+    fn challenge(proof: Label, context: &TxContext) -> Scalar {
+        let mut transcript = ProofTranscript::with_context(proof, context);
+        transcript.append_message(MSG, b"statement");
+        transcript.challenge_scalar(CHALLENGE)
+    }
+
+    #[test]
+    fn transcript_test() {
+        let context = TxContext::new(ChainId::MAINNET).bind(TX, b"tx 1");
+        let c = challenge(PROOF, &context);
+        assert_eq!(c, challenge(PROOF, &context.clone()));
+
+        //the proof name, the chain and every bound item change the challenge
+        assert_ne!(c, challenge(OTHER_PROOF, &context));
+        assert_ne!(
+            c,
+            challenge(PROOF, &TxContext::new(ChainId::TESTNET).bind(TX, b"tx 1"))
+        );
+        assert_ne!(
+            c,
+            challenge(PROOF, &TxContext::new(ChainId::MAINNET).bind(TX, b"tx 2"))
+        );
+        assert_ne!(c, challenge(PROOF, &TxContext::new(ChainId::MAINNET)));
+        assert_ne!(c, challenge(PROOF, &context.clone().bind(TX, b"")));
+
+        //a transcript without context differs from one with an empty context
+        let mut transcript = ProofTranscript::new(PROOF);
+        transcript.append_message(MSG, b"statement");
+        assert_ne!(
+            transcript.challenge_scalar(CHALLENGE),
+            challenge(PROOF, &TxContext::default())
+        );
     }
 }
@@ -0,0 +1,163 @@
+use crate::error::WalletError;
+
+//how a wallet picks the outputs to spend. Every input of a `SignTx` is public and
+//linked to the others, so the strategies trade fewer inputs against less change.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum CoinSelection {
+    //biggest outputs first, the fewest inputs for any amount
+    #[default]
+    LargestFirst,
+    //a single output whenever one is enough, the smallest such one so large
+    //outputs are not revealed and linked needlessly, otherwise largest-first
+    PrivacyAware,
+    //the set leaving the least change, ideally an exact match
+    MinimizeChange,
+}
+
+impl CoinSelection {
+    //indices into `balances` of the outputs to spend, they add up to at least `target`
+    pub fn select(&self, balances: &[u64], target: u64) -> Result<Vec<usize>, WalletError> {
+        let available = balances.iter().fold(0u64, |sum, x| sum.saturating_add(*x));
+        if available < target || balances.is_empty() {
+            return Err(WalletError::InsufficientFunds {
+                available,
+                required: target,
+            });
+        }
+
+        let selected = match self {
+            CoinSelection::LargestFirst => largest_first(balances, target),
+            CoinSelection::PrivacyAware => smallest_sufficient(balances, target)
+                .map(|x| vec![x])
+                .unwrap_or_else(|| largest_first(balances, target)),
+            CoinSelection::MinimizeChange => minimize_change(balances, target),
+        };
+        Ok(selected)
+    }
+}
+
+fn largest_first(balances: &[u64], target: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..balances.len()).collect();
+    order.sort_by(|a, b| balances[*b].cmp(&balances[*a]));
+
+    let mut selected = Vec::new();
+    let mut sum = 0u64;
+    for x in order {
+        if sum >= target && !selected.is_empty() {
+            break;
+        }
+        sum = sum.saturating_add(balances[x]);
+        selected.push(x);
+    }
+    selected
+}
+
+fn smallest_sufficient(balances: &[u64], target: u64) -> Option<usize> {
+    (0..balances.len())
+        .filter(|x| balances[*x] >= target)
+        .min_by_key(|x| balances[*x])
+}
+
+//takes every output, largest first, that still fits under the target and tops up
+//with the smallest output that reaches it, then drops inputs the target does not
+//need. The result is compared against the best single output.
+fn minimize_change(balances: &[u64], target: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..balances.len()).collect();
+    order.sort_by(|a, b| balances[*b].cmp(&balances[*a]));
+
+    let mut selected = Vec::new();
+    let mut sum = 0u64;
+    for x in &order {
+        if balances[*x] <= target - sum {
+            sum += balances[*x];
+            selected.push(*x);
+        }
+    }
+    if sum < target || selected.is_empty() {
+        let rest: Vec<u64> = balances
+            .iter()
+            .enumerate()
+            .map(|(i, x)| if selected.contains(&i) { 0 } else { *x })
+            .collect();
+        //every skipped output overshoots on its own, so one of them reaches the target
+        let top_up = match smallest_sufficient(&rest, target - sum) {
+            Some(x) => x,
+            None => return largest_first(balances, target),
+        };
+        //a saturated sum only understates the total, so no needed input is dropped
+        sum = sum.saturating_add(balances[top_up]);
+        selected.push(top_up);
+
+        selected.sort_by_key(|x| balances[*x]);
+        let mut i = 0;
+        while i < selected.len() {
+            let balance = balances[selected[i]];
+            if selected.len() > 1 && sum - balance >= target {
+                sum -= balance;
+                selected.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    match smallest_sufficient(balances, target) {
+        Some(x) if balances[x] < sum || (balances[x] == sum && selected.len() > 1) => vec![x],
+        _ => selected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coin_selection_test() {
+        let balances = [5, 40, 12, 30, 8];
+        let select = |strategy: CoinSelection, target| {
+            let mut selected = strategy.select(&balances, target).unwrap();
+            selected.sort();
+            selected
+        };
+
+        assert_eq!(select(CoinSelection::LargestFirst, 10), vec![1]);
+        assert_eq!(select(CoinSelection::LargestFirst, 50), vec![1, 3]);
+        assert_eq!(select(CoinSelection::PrivacyAware, 10), vec![2]);
+        assert_eq!(select(CoinSelection::PrivacyAware, 41), vec![1, 3]);
+        assert_eq!(select(CoinSelection::MinimizeChange, 8), vec![4]);
+        assert_eq!(select(CoinSelection::MinimizeChange, 20), vec![2, 4]);
+        assert_eq!(select(CoinSelection::MinimizeChange, 52), vec![1, 2]);
+        //30 topped up with 5 beats a single 40
+        assert_eq!(select(CoinSelection::MinimizeChange, 33), vec![0, 3]);
+        assert_eq!(select(CoinSelection::MinimizeChange, 39), vec![1]);
+        assert_eq!(
+            select(CoinSelection::MinimizeChange, 95),
+            vec![0, 1, 2, 3, 4]
+        );
+        assert_eq!(select(CoinSelection::LargestFirst, 0), vec![1]);
+
+        assert_eq!(
+            CoinSelection::MinimizeChange.select(&balances, 96),
+            Err(WalletError::InsufficientFunds {
+                available: 95,
+                required: 96,
+            })
+        );
+        assert!(CoinSelection::LargestFirst.select(&[], 0).is_err());
+    }
+
+    #[test]
+    fn coin_selection_overflow_test() {
+        let balances = [u64::MAX - 1, u64::MAX - 1, 3];
+        let select = |strategy: CoinSelection, target| {
+            let mut selected = strategy.select(&balances, target).unwrap();
+            selected.sort();
+            selected
+        };
+
+        assert_eq!(select(CoinSelection::LargestFirst, u64::MAX), vec![0, 1]);
+        assert_eq!(select(CoinSelection::PrivacyAware, u64::MAX), vec![0, 1]);
+        assert_eq!(select(CoinSelection::MinimizeChange, u64::MAX), vec![0, 2]);
+        assert_eq!(select(CoinSelection::MinimizeChange, u64::MAX - 1), vec![0]);
+    }
+}
@@ -0,0 +1,87 @@
+use ct_account::error::AccountError;
+use ct_ledger::error::LedgerError;
+use ct_utils::error::UtilsError;
+use std::fmt;
+use std::io::ErrorKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletError {
+    Codec(UtilsError),
+    Account(AccountError),
+    Ledger(LedgerError),
+    //`required` is the amount plus the fee
+    InsufficientFunds { available: u64, required: u64 },
+    //a watch-only wallet has no spend key
+    WatchOnly,
+    BlockMissing(u64),
+    BlockOutOfScanOrder { expected: u64, found: u64 },
+    //the saved state was written for other keys
+    WrongAccount,
+    OutputNotOwned,
+    Io(ErrorKind),
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalletError::Codec(e) => write!(f, "{}", e),
+            WalletError::Account(e) => write!(f, "{}", e),
+            WalletError::Ledger(e) => write!(f, "{}", e),
+            WalletError::InsufficientFunds {
+                available,
+                required,
+            } => write!(
+                f,
+                "insufficient funds: {} available, {} required",
+                available, required
+            ),
+            WalletError::WatchOnly => write!(f, "watch-only wallet cannot spend"),
+            WalletError::BlockMissing(height) => {
+                write!(f, "block {} missing from ledger", height)
+            }
+            WalletError::BlockOutOfScanOrder { expected, found } => write!(
+                f,
+                "block out of scan order: expected height {}, found {}",
+                expected, found
+            ),
+            WalletError::WrongAccount => write!(f, "wallet belongs to another account"),
+            WalletError::OutputNotOwned => write!(f, "wallet output not owned by account"),
+            WalletError::Io(kind) => write!(f, "wallet io error: {:?}", kind),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WalletError::Codec(e) => Some(e),
+            WalletError::Account(e) => Some(e),
+            WalletError::Ledger(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<UtilsError> for WalletError {
+    fn from(e: UtilsError) -> WalletError {
+        WalletError::Codec(e)
+    }
+}
+
+impl From<AccountError> for WalletError {
+    fn from(e: AccountError) -> WalletError {
+        WalletError::Account(e)
+    }
+}
+
+impl From<LedgerError> for WalletError {
+    fn from(e: LedgerError) -> WalletError {
+        WalletError::Ledger(e)
+    }
+}
+
+impl From<std::io::Error> for WalletError {
+    fn from(e: std::io::Error) -> WalletError {
+        WalletError::Io(e.kind())
+    }
+}
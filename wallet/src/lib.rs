@@ -0,0 +1,3 @@
+pub mod coin_selection;
+pub mod error;
+pub mod wallet;
@@ -0,0 +1,634 @@
+use crate::coin_selection::CoinSelection;
+use crate::error::WalletError;
+use ct_account::account::{Account, Recipient, ViewAccount};
+use ct_account::confidential_transaction::{ConfidentialTransaction, SignTx};
+use ct_ledger::block::Block;
+use ct_ledger::ledger::{output_id, Ledger, OutputId};
+use ct_ledger::storage::Storage;
+use ct_mlsag::material::KeyPair;
+use ct_token::token::TokenSecret;
+use ct_utils::codec::{self, Decode, Encode, Reader, Writer};
+use ct_utils::error::UtilsError;
+use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+//keys a wallet scans with. A watch-only wallet sees incoming outputs and their
+//amounts, but without the spend key it has no one-time private keys and so no key
+//images: spends through a ring are invisible to it and its balance only drops
+//for `SignTx` spends, which reveal their input.
+#[derive(Debug, Clone)]
+pub enum WalletKeys {
+    Full(Account),
+    ViewOnly(ViewAccount),
+}
+
+impl WalletKeys {
+    pub fn to_view_account(&self) -> ViewAccount {
+        match self {
+            WalletKeys::Full(account) => account.to_view_account(),
+            WalletKeys::ViewOnly(view_account) => view_account.clone(),
+        }
+    }
+}
+
+impl From<Account> for WalletKeys {
+    fn from(account: Account) -> WalletKeys {
+        WalletKeys::Full(account)
+    }
+}
+
+impl From<ViewAccount> for WalletKeys {
+    fn from(view_account: ViewAccount) -> WalletKeys {
+        WalletKeys::ViewOnly(view_account)
+    }
+}
+
+//an output the wallet can open. The one-time private key, the key image and the
+//token secret are all derived again from the keys, so only the output itself,
+//the height it was found at and the spent flag are persisted.
+#[derive(Clone)]
+pub struct OwnedOutput {
+    pub output: ConfidentialTransaction,
+    //`None` in a watch-only wallet
+    pub private_key: Option<Scalar>,
+    pub key_image: Option<RistrettoPoint>,
+    pub secret: TokenSecret,
+    //`None` for outputs picked up from the UTXO set rather than from a block
+    pub height: Option<u64>,
+    pub spent: bool,
+}
+
+impl OwnedOutput {
+    pub fn get_balance(&self) -> u64 {
+        self.secret.balance
+    }
+}
+
+//follows the ledger block by block for one account. `scan_height` is the next
+//block to look at, so a saved wallet only has to process blocks added since.
+//Reorgs are not handled, blocks are assumed final once scanned.
+pub struct Wallet {
+    keys: WalletKeys,
+    view_account: ViewAccount,
+    scan_height: u64,
+    outputs: HashMap<OutputId, OwnedOutput>,
+}
+
+impl Wallet {
+    pub fn new<K: Into<WalletKeys>>(keys: K) -> Wallet {
+        let keys = keys.into();
+        Wallet {
+            view_account: keys.to_view_account(),
+            keys,
+            scan_height: 0,
+            outputs: HashMap::new(),
+        }
+    }
+
+    //`None` for a watch-only wallet, which therefore cannot build spends
+    pub fn get_account(&self) -> Option<&Account> {
+        match &self.keys {
+            WalletKeys::Full(account) => Some(account),
+            WalletKeys::ViewOnly(_) => None,
+        }
+    }
+
+    pub fn get_view_account(&self) -> &ViewAccount {
+        &self.view_account
+    }
+
+    pub fn is_watch_only(&self) -> bool {
+        self.get_account().is_none()
+    }
+
+    pub fn get_scan_height(&self) -> u64 {
+        self.scan_height
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &OwnedOutput> {
+        self.outputs.values()
+    }
+
+    pub fn spendable(&self) -> impl Iterator<Item = &OwnedOutput> {
+        self.outputs.values().filter(|x| !x.spent)
+    }
+
+    //genesis outputs skip the supply check, so owned outputs may add up past u64::MAX
+    pub fn balance(&self) -> u64 {
+        self.spendable()
+            .fold(0u64, |sum, x| sum.saturating_add(x.get_balance()))
+    }
+
+    //spends enough unspent outputs to pay `amount` plus `fee` to `to` in one
+    //transaction. The outputs stay unspent here, they are marked once the
+    //transaction shows up in a scanned block.
+    pub fn transfer<R: Recipient>(
+        &self,
+        to: &R,
+        amount: u64,
+        fee: u64,
+        strategy: CoinSelection,
+    ) -> Result<SignTx, WalletError> {
+        let account = self.get_account().ok_or(WalletError::WatchOnly)?;
+        let required = amount
+            .checked_add(fee)
+            .ok_or(WalletError::InsufficientFunds {
+                available: self.balance(),
+                required: u64::MAX,
+            })?;
+
+        //sorted by output id, so the input order says nothing about the strategy
+        let mut candidates: Vec<(OutputId, &OwnedOutput)> = self
+            .outputs
+            .iter()
+            .filter(|(_, x)| !x.spent)
+            .map(|(id, x)| (*id, x))
+            .collect();
+        candidates.sort_by_key(|(id, _)| *id);
+        let balances: Vec<u64> = candidates.iter().map(|(_, x)| x.get_balance()).collect();
+
+        let mut selected = strategy.select(&balances, required)?;
+        selected.sort();
+        let inputs: Vec<ConfidentialTransaction> = selected
+            .into_iter()
+            .map(|x| candidates[x].1.output.clone())
+            .collect();
+        Ok(SignTx::new(&inputs, account, to, amount, fee)?)
+    }
+
+    //process every block from the scan height up to the ledger tip, returns the
+    //number of newly found outputs
+    pub fn scan<S: Storage>(&mut self, ledger: &Ledger<S>) -> Result<usize, WalletError> {
+        let tip = match ledger.tip() {
+            Some(tip) => tip.height,
+            None => return Ok(0),
+        };
+        let mut found = 0;
+        while self.scan_height <= tip {
+            let block = ledger
+                .get_block(self.scan_height)
+                .ok_or(WalletError::BlockMissing(self.scan_height))?;
+            found += self.scan_block(&block)?;
+        }
+        Ok(found)
+    }
+
+    pub fn scan_block(&mut self, block: &Block) -> Result<usize, WalletError> {
+        if block.header.height != self.scan_height {
+            return Err(WalletError::BlockOutOfScanOrder {
+                expected: self.scan_height,
+                found: block.header.height,
+            });
+        }
+
+        let mut found = 0;
+        for tx in &block.transactions {
+            for id in tx.spent_outputs() {
+                if let Some(owned) = self.outputs.get_mut(&id) {
+                    owned.spent = true;
+                }
+            }
+            let key_images = tx.key_images()?;
+            for owned in self.outputs.values_mut() {
+                let key_image = owned.key_image.map(|x| x.compress().to_bytes());
+                if key_image.is_some_and(|x| key_images.contains(&x)) {
+                    owned.spent = true;
+                }
+            }
+            for output in tx.outputs() {
+                if self.receive(output, Some(block.header.height)) {
+                    found += 1;
+                }
+            }
+        }
+        self.scan_height += 1;
+        Ok(found)
+    }
+
+    //pick up owned outputs from the current UTXO set, for outputs that never went
+    //through a block such as genesis allocations. Ring spends leave their input in
+    //the set, so those are recognized by their key image.
+    pub fn scan_utxos<S: Storage>(&mut self, ledger: &Ledger<S>) -> usize {
+        let found = ledger.outputs().filter(|x| self.receive(x, None)).count();
+        for owned in self.outputs.values_mut() {
+            if owned
+                .key_image
+                .is_some_and(|x| ledger.is_key_image_spent(&x))
+            {
+                owned.spent = true;
+            }
+        }
+        found
+    }
+
+    //returns whether `output` belongs to the account and was not known yet
+    fn receive(&mut self, output: &ConfidentialTransaction, height: Option<u64>) -> bool {
+        let id = output_id(&output.one_time_account);
+        if self.outputs.contains_key(&id) {
+            return false;
+        }
+        match Self::open_output(&self.keys, &self.view_account, output, height, false) {
+            Some(owned) => {
+                self.outputs.insert(id, owned);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn open_output(
+        keys: &WalletKeys,
+        view_account: &ViewAccount,
+        output: &ConfidentialTransaction,
+        height: Option<u64>,
+        spent: bool,
+    ) -> Option<OwnedOutput> {
+        if !output
+            .one_time_account
+            .belongs_to(view_account, output.blind_point, output.derivation)
+        {
+            return None;
+        }
+        let secret = output
+            .decrypt_token_secrt(view_account.get_view_private_key())
+            .ok()?;
+
+        let private_key = match keys {
+            WalletKeys::Full(account) => Some(
+                output
+                    .one_time_account
+                    .get_private_key(account, output.blind_point, output.derivation)
+                    .ok()?,
+            ),
+            WalletKeys::ViewOnly(_) => None,
+        };
+        let key_image = private_key.map(|private_key| {
+            KeyPair {
+                public_key: output.one_time_account.get_point(),
+                private_key,
+            }
+            .compute_key_images()
+        });
+        Some(OwnedOutput {
+            output: output.clone(),
+            private_key,
+            key_image,
+            secret,
+            height,
+            spent,
+        })
+    }
+
+    //the state file is bound to the public keys so it cannot be loaded into the
+    //wrong wallet, a full wallet's file can be opened watch-only and vice versa
+    pub fn to_bytes(&self) -> Vec<u8> {
+        codec::to_bytes(&StoredWallet {
+            view_public_key: self.view_account.get_view_public_key(),
+            spend_public_key: self.view_account.get_spend_public_key(),
+            scan_height: self.scan_height,
+            outputs: self
+                .outputs
+                .values()
+                .map(|x| StoredOutput {
+                    output: x.output.clone(),
+                    height: x.height,
+                    spent: x.spent,
+                })
+                .collect(),
+        })
+    }
+
+    pub fn from_bytes<K: Into<WalletKeys>>(keys: K, bytes: &[u8]) -> Result<Wallet, WalletError> {
+        let stored: StoredWallet = codec::from_bytes(bytes)?;
+        let mut wallet = Wallet::new(keys);
+        if stored.view_public_key != wallet.view_account.get_view_public_key()
+            || stored.spend_public_key != wallet.view_account.get_spend_public_key()
+        {
+            return Err(WalletError::WrongAccount);
+        }
+
+        for x in stored.outputs {
+            let owned = Self::open_output(
+                &wallet.keys,
+                &wallet.view_account,
+                &x.output,
+                x.height,
+                x.spent,
+            )
+            .ok_or(WalletError::OutputNotOwned)?;
+            wallet
+                .outputs
+                .insert(output_id(&x.output.one_time_account), owned);
+        }
+        wallet.scan_height = stored.scan_height;
+        Ok(wallet)
+    }
+
+    //write to a temporary file first so a crash never leaves a half written wallet
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), WalletError> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, self.to_bytes())
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(WalletError::from)
+    }
+
+    //a missing file is a fresh wallet that scans from height 0
+    pub fn open<K: Into<WalletKeys>, P: AsRef<Path>>(
+        keys: K,
+        path: P,
+    ) -> Result<Wallet, WalletError> {
+        match fs::read(path) {
+            Ok(bytes) => Wallet::from_bytes(keys, &bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Wallet::new(keys)),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+struct StoredOutput {
+    output: ConfidentialTransaction,
+    height: Option<u64>,
+    spent: bool,
+}
+
+impl Encode for StoredOutput {
+    fn encode(&self, writer: &mut Writer) {
+        self.output.encode(writer);
+        match self.height {
+            Some(height) => {
+                writer.write_u8(1);
+                writer.write_u64(height);
+            }
+            None => writer.write_u8(0),
+        }
+        writer.write_u8(self.spent as u8);
+    }
+}
+
+impl Decode for StoredOutput {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        let output = ConfidentialTransaction::decode(reader)?;
+        let height = match reader.read_u8()? {
+            0 => None,
+            1 => Some(reader.read_u64()?),
+            _ => return Err(UtilsError::Malformed("wallet output")),
+        };
+        let spent = match reader.read_u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(UtilsError::Malformed("wallet output")),
+        };
+        Ok(StoredOutput {
+            output,
+            height,
+            spent,
+        })
+    }
+}
+
+struct StoredWallet {
+    view_public_key: RistrettoPoint,
+    spend_public_key: RistrettoPoint,
+    scan_height: u64,
+    outputs: Vec<StoredOutput>,
+}
+
+impl Encode for StoredWallet {
+    fn encode(&self, writer: &mut Writer) {
+        self.view_public_key.encode(writer);
+        self.spend_public_key.encode(writer);
+        writer.write_u64(self.scan_height);
+        self.outputs.encode(writer);
+    }
+}
+
+impl Decode for StoredWallet {
+    fn decode(reader: &mut Reader) -> Result<Self, UtilsError> {
+        Ok(StoredWallet {
+            view_public_key: RistrettoPoint::decode(reader)?,
+            spend_public_key: RistrettoPoint::decode(reader)?,
+            scan_height: reader.read_u64()?,
+            outputs: Vec::decode(reader)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ct_account::address::Network;
+    use ct_account::mint_transaction::MintTx;
+    use ct_account::ring_confidential_transaction::RingCT;
+    use ct_ledger::transaction::Transaction;
+
+    fn apply(ledger: &mut Ledger, transactions: Vec<Transaction>) {
+        let (height, prev_hash) = match ledger.tip() {
+            Some(tip) => (tip.height + 1, tip.hash()),
+            None => (0, [0; 32]),
+        };
+        ledger
+            .apply_block(&Block::new(height, prev_hash, transactions))
+            .unwrap();
+    }
+
+    fn mint(issuer: &Account, to: &Account, amount: u64) -> Transaction {
+        let address = to.to_address(Network::Testnet);
+        Transaction::Mint(Box::new(MintTx::new(issuer, &address, amount).unwrap()))
+    }
+
+    #[test]
+    fn wallet_scan_test() {
+        let (issuer, alice, bob) = (Account::new(), Account::new(), Account::new());
+        let mut ledger = Ledger::new();
+        ledger.authorize_issuer(issuer.get_spend_public_key());
+        apply(
+            &mut ledger,
+            vec![mint(&issuer, &alice, 100), mint(&issuer, &bob, 50)],
+        );
+
+        let mut wallet = Wallet::new(alice.clone());
+        assert_eq!(wallet.scan(&ledger), Ok(1));
+        assert_eq!(wallet.balance(), 100);
+        assert_eq!(wallet.get_scan_height(), 1);
+
+        //spend to bob, the change comes back to alice in the next block
+        let input = wallet.spendable().next().unwrap().output.clone();
+        let tx = input.transfer(&alice, &bob, 30, 2).unwrap();
+        apply(&mut ledger, vec![Transaction::Sign(Box::new(tx))]);
+        assert_eq!(wallet.scan(&ledger), Ok(1));
+        assert_eq!(wallet.balance(), 68);
+        assert_eq!(wallet.outputs().filter(|x| x.spent).count(), 1);
+
+        //bob spends his mint through a ring, only the key image shows the spend
+        let mut bob_wallet = Wallet::new(bob.clone());
+        bob_wallet.scan(&ledger).unwrap();
+        assert_eq!(bob_wallet.balance(), 80);
+        let minted = bob_wallet
+            .spendable()
+            .find(|x| x.height == Some(0))
+            .unwrap()
+            .output
+            .clone();
+        let decoy = wallet.spendable().next().unwrap().output.clone();
+        let ring_ct = RingCT {
+            ownership_account: bob.clone(),
+            input_tx: vec![minted],
+            output_account: vec![(alice.clone(), 50)],
+            decoys: vec![vec![decoy]],
+            fee: 0,
+        };
+        apply(
+            &mut ledger,
+            vec![Transaction::Ring(Box::new(ring_ct.transfer().unwrap()))],
+        );
+        bob_wallet.scan(&ledger).unwrap();
+        assert_eq!(bob_wallet.balance(), 30);
+        wallet.scan(&ledger).unwrap();
+        assert_eq!(wallet.balance(), 118);
+    }
+
+    #[test]
+    fn wallet_persist_test() {
+        let (issuer, alice) = (Account::new(), Account::new());
+        let mut ledger = Ledger::new();
+        ledger.authorize_issuer(issuer.get_spend_public_key());
+        apply(&mut ledger, vec![mint(&issuer, &alice, 10)]);
+
+        let path = std::env::temp_dir().join(format!("ct_wallet_{}", std::process::id()));
+        let mut wallet = Wallet::open(alice.clone(), &path).unwrap();
+        wallet.scan(&ledger).unwrap();
+        wallet.save(&path).unwrap();
+
+        //the reopened wallet resumes at the saved height and only sees new blocks
+        apply(&mut ledger, vec![mint(&issuer, &alice, 5)]);
+        let mut wallet = Wallet::open(alice.clone(), &path).unwrap();
+        assert_eq!(wallet.get_scan_height(), 1);
+        assert_eq!(wallet.balance(), 10);
+        assert_eq!(wallet.scan(&ledger), Ok(1));
+        assert_eq!(wallet.balance(), 15);
+
+        assert_eq!(
+            Wallet::open(Account::new(), &path).err(),
+            Some(WalletError::WrongAccount)
+        );
+        fs::remove_file(&path).unwrap();
+
+        //genesis style outputs are only found through the UTXO set
+        let mut ledger = Ledger::new();
+        ledger
+            .add_output(ConfidentialTransaction::new(&alice, 7).unwrap())
+            .unwrap();
+        let mut wallet = Wallet::new(alice);
+        assert_eq!(wallet.scan(&ledger), Ok(0));
+        assert_eq!(wallet.scan_utxos(&ledger), 1);
+        assert_eq!(wallet.balance(), 7);
+    }
+
+    #[test]
+    fn wallet_balance_overflow_test() {
+        let (alice, bob) = (Account::new(), Account::new());
+        let mut ledger = Ledger::new();
+        for _ in 0..2 {
+            ledger
+                .add_output(ConfidentialTransaction::new(&alice, u64::MAX).unwrap())
+                .unwrap();
+        }
+        let mut wallet = Wallet::new(alice);
+        assert_eq!(wallet.scan_utxos(&ledger), 2);
+        assert_eq!(wallet.balance(), u64::MAX);
+        assert_eq!(
+            wallet
+                .transfer(&bob, u64::MAX, 1, CoinSelection::default())
+                .err(),
+            Some(WalletError::InsufficientFunds {
+                available: u64::MAX,
+                required: u64::MAX,
+            })
+        );
+    }
+
+    #[test]
+    fn wallet_transfer_test() {
+        let (issuer, alice, bob) = (Account::new(), Account::new(), Account::new());
+        let mut ledger = Ledger::new();
+        ledger.authorize_issuer(issuer.get_spend_public_key());
+        apply(
+            &mut ledger,
+            vec![
+                mint(&issuer, &alice, 30),
+                mint(&issuer, &alice, 50),
+                mint(&issuer, &alice, 20),
+            ],
+        );
+        let mut wallet = Wallet::new(alice.clone());
+        wallet.scan(&ledger).unwrap();
+
+        //no single output covers 75, the largest two do
+        let bob_address = bob.to_address(Network::Testnet);
+        let tx = wallet
+            .transfer(&bob_address, 75, 1, CoinSelection::LargestFirst)
+            .unwrap();
+        assert_eq!(tx.inputs.len(), 2);
+        apply(&mut ledger, vec![Transaction::Sign(Box::new(tx))]);
+        wallet.scan(&ledger).unwrap();
+        assert_eq!(wallet.balance(), 24);
+        let mut bob_wallet = Wallet::new(bob.clone());
+        bob_wallet.scan(&ledger).unwrap();
+        assert_eq!(bob_wallet.balance(), 75);
+
+        //change 4 and the untouched 20 pay 24 exactly
+        let tx = wallet
+            .transfer(&bob, 23, 1, CoinSelection::MinimizeChange)
+            .unwrap();
+        assert_eq!(tx.inputs.len(), 2);
+        tx.verify().unwrap();
+
+        assert_eq!(
+            wallet
+                .transfer(&bob, 24, 1, CoinSelection::PrivacyAware)
+                .err(),
+            Some(WalletError::InsufficientFunds {
+                available: 24,
+                required: 25,
+            })
+        );
+        assert_eq!(
+            Wallet::new(alice.to_view_account())
+                .transfer(&bob, 1, 0, CoinSelection::LargestFirst)
+                .err(),
+            Some(WalletError::WatchOnly)
+        );
+    }
+
+    #[test]
+    fn watch_only_wallet_test() {
+        let (issuer, alice, bob) = (Account::new(), Account::new(), Account::new());
+        let mut ledger = Ledger::new();
+        ledger.authorize_issuer(issuer.get_spend_public_key());
+        apply(&mut ledger, vec![mint(&issuer, &alice, 40)]);
+
+        //the view key alone sees the payment and its amount
+        let mut watch = Wallet::new(alice.to_view_account());
+        assert!(watch.is_watch_only());
+        assert_eq!(watch.scan(&ledger), Ok(1));
+        assert_eq!(watch.balance(), 40);
+        let owned = watch.spendable().next().unwrap();
+        assert!(owned.private_key.is_none() && owned.key_image.is_none());
+        assert!(Wallet::new(bob.to_view_account()).scan(&ledger) == Ok(0));
+
+        //a plain spend reveals its input, so the watch-only wallet notices it
+        let input = owned.output.clone();
+        let tx = input.transfer(&alice, &bob, 15, 0).unwrap();
+        apply(&mut ledger, vec![Transaction::Sign(Box::new(tx))]);
+        watch.scan(&ledger).unwrap();
+        assert_eq!(watch.balance(), 25);
+
+        //the saved state can be upgraded to a full wallet
+        let full = Wallet::from_bytes(alice.clone(), &watch.to_bytes()).unwrap();
+        assert!(full.spendable().all(|x| x.private_key.is_some()));
+        assert_eq!(full.balance(), 25);
+    }
+}